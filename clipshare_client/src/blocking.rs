@@ -0,0 +1,114 @@
+/*
+* Copyright (C) 2023 Rastislav Kish
+*
+* This program is free software: you can redistribute it and/or modify
+* it under the terms of the GNU General Public License as published by
+* the Free Software Foundation, version 3.
+*
+* This program is distributed in the hope that it will be useful,
+* but WITHOUT ANY WARRANTY; without even the implied warranty of
+* MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+* GNU General Public License for more details.
+*
+* You should have received a copy of the GNU General Public License
+* along with this program. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! A synchronous API, running the asynchronous one on a runtime owned by the clipboard.
+
+use std::net::SocketAddr;
+
+use tokio::runtime::{Builder, Runtime};
+
+use crate::{ClientOptions, Progress, ServerInfo, SharedClipboardContent};
+
+/// A blocking counterpart of crate::Clipshare.
+pub struct Clipshare {
+    inner: crate::Clipshare,
+    runtime: Runtime,
+    }
+impl Clipshare {
+
+    /// Creates a new instance of Clipshare with the default network settings.
+    pub fn new(host: &str) -> Result<Clipshare, anyhow::Error> {
+        Clipshare::with_options(host, &ClientOptions::default())
+        }
+
+    /// Creates a new instance of Clipshare with the given network settings, see crate::Clipshare::with_options.
+    pub fn with_options(host: &str, options: &ClientOptions) -> Result<Clipshare, anyhow::Error> {
+        let runtime=Builder::new_current_thread()
+        .enable_all()
+        .build()?;
+        let inner=crate::Clipshare::with_options(host, options)?;
+
+        Ok(Clipshare { inner, runtime })
+        }
+
+    /// Capabilities and limits of the server, None if the server predates the versioned API or could not be reached.
+    pub fn server_info(&self) -> Option<ServerInfo> {
+        self.runtime.block_on(self.inner.server_info())
+        }
+
+    /// Whether the server responds, asking it again if it could not be reached before.
+    pub fn is_reachable(&self) -> bool {
+        self.runtime.block_on(self.inner.is_reachable())
+        }
+
+    /// Resolves the host name of the server, returning its addresses.
+    pub fn resolve_host(&self) -> Result<Vec<SocketAddr>, anyhow::Error> {
+        self.runtime.block_on(self.inner.resolve_host())
+        }
+
+    /// Checks the server can be connected to, including the TLS handshake and the validation of its certificate.
+    pub fn check_connection(&self) -> Result<(), anyhow::Error> {
+        self.runtime.block_on(self.inner.check_connection())
+        }
+    }
+
+/// A blocking counterpart of crate::SharedClipboard.
+pub struct SharedClipboard {
+    inner: crate::SharedClipboard,
+    runtime: Runtime,
+    }
+impl SharedClipboard {
+
+    /// Creates a new instance of SharedClipboard.
+    pub fn new(name: &str, clipshare: Clipshare, password: &str) -> SharedClipboard {
+        let inner=crate::SharedClipboard::new(name, clipshare.inner, password);
+
+        SharedClipboard { inner, runtime: clipshare.runtime }
+        }
+
+    pub fn name(&self) -> &str {
+        self.inner.name()
+        }
+    pub fn clipboard_id(&self) -> &str {
+        self.inner.clipboard_id()
+        }
+
+    /// Gets the content of the shared clipboard, reporting the download progress if requested.
+    pub fn get_content(&self, progress: Option<Progress>) -> Result<SharedClipboardContent, anyhow::Error> {
+        self.runtime.block_on(self.inner.get_content(progress))
+        }
+
+    /// Checks the shared clipboard can be downloaded and decrypted with its password, see crate::SharedClipboard::check_content.
+    pub fn check_content(&self) -> Result<(), anyhow::Error> {
+        self.runtime.block_on(self.inner.check_content())
+        }
+
+    /// Sets the content of the shared clipboard, reporting the upload progress if requested.
+    pub fn set_content(&self, content: SharedClipboardContent, progress: Option<Progress>) -> Result<(), anyhow::Error> {
+        self.runtime.block_on(self.inner.set_content(content, progress))
+        }
+
+    /// Encrypts content with the password of the shared clipboard, for keeping it elsewhere.
+    pub fn seal(&self, content: &SharedClipboardContent) -> Result<Vec<u8>, anyhow::Error> {
+        self.inner.seal(content)
+        }
+
+    /// Decrypts content encrypted by seal.
+    pub fn unseal(&self, sealed_content: &[u8]) -> Result<SharedClipboardContent, anyhow::Error> {
+        self.inner.unseal(sealed_content)
+        }
+    }
+
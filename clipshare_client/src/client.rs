@@ -0,0 +1,615 @@
+/*
+* Copyright (C) 2023 Rastislav Kish
+*
+* This program is free software: you can redistribute it and/or modify
+* it under the terms of the GNU General Public License as published by
+* the Free Software Foundation, version 3.
+*
+* This program is distributed in the hope that it will be useful,
+* but WITHOUT ANY WARRANTY; without even the implied warranty of
+* MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+* GNU General Public License for more details.
+*
+* You should have received a copy of the GNU General Public License
+* along with this program. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use std::io::{self, Cursor, Read};
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::Context;
+use base64::{Engine, engine::general_purpose as base64_eng};
+use bytes::Bytes;
+use derive_getters::Getters;
+use futures_util::stream::{self, BoxStream, StreamExt};
+use rand::{Rng, distributions::Alphanumeric};
+use reqwest::header;
+use reqwest::StatusCode;
+use serde::{Serialize, Deserialize};
+use sha2::{Digest, Sha256};
+
+use crate::content::SharedClipboardContent;
+use crate::cryptography::{
+    encrypt, decrypt, encrypted_length, encrypt_stream, encrypted_stream_length, calculate_pseudosalted_password_hash,
+    Decryptor, STREAM_THRESHOLD,
+    };
+use crate::error::ClipshareError;
+use crate::options::ClientOptions;
+use crate::retry::RetryPolicy;
+
+const OCTET_STREAM: &str="application/octet-stream";
+const MANIFEST_CONTENT_TYPE: &str="application/vnd.clipshare.manifest+json";
+
+/// How long to wait for the capabilities of a server, so an unreachable server doesn't hold up the first request for long.
+const SERVER_INFO_TIMEOUT: Duration=Duration::from_secs(5);
+/// Size of the pieces request bodies are sent in.
+const BODY_CHUNK_SIZE: usize=64*1024;
+
+/// A callback reporting the progress of a transfer, receiving the number of transferred bytes and the total number of bytes, if known.
+pub type Progress=Box<dyn FnMut(u64, Option<u64>)+Send>;
+/// The progress callback shared by all requests of a transfer, so uploads split into several requests can report the progress of the whole transfer.
+type SharedProgress=Arc<Mutex<Progress>>;
+
+/// The raw content of a shared clipboard, downloaded as it's being consumed.
+pub type ContentStream=BoxStream<'static, Result<Bytes, anyhow::Error>>;
+
+/// A part of a multi-part clipboard as listed in its manifest.
+#[derive(Serialize, Deserialize)]
+struct ManifestPart {
+    size: u64,
+    sha256: String,
+    }
+
+/// The content of a multi-part clipboard, listing its parts uploaded under upload_id in order.
+#[derive(Serialize, Deserialize)]
+struct Manifest {
+    upload_id: String,
+    parts: Vec<ManifestPart>,
+    }
+
+/// The state of a download, going through the parts of a multi-part clipboard one after another.
+/// Every part is checked against its hash from the manifest once read completely.
+/// A single-part clipboard is downloaded as a lone part without a hash.
+struct Download {
+    client: reqwest::Client,
+    parts_url: String,
+    parts: Vec<ManifestPart>,
+    next_part: usize,
+    current_part: Option<(reqwest::Response, Option<Sha256>)>,
+    transferred: u64,
+    total: Option<u64>,
+    progress: Option<Progress>,
+    }
+impl Download {
+
+    async fn next_chunk(mut self) -> Result<Option<(Bytes, Download)>, anyhow::Error> {
+        loop {
+            if let Some((res, hasher))=self.current_part.as_mut() {
+                if let Some(chunk)=res.chunk().await.context(ClipshareError::Network)? {
+                    if let Some(hasher)=hasher {
+                        hasher.update(&chunk);
+                        }
+
+                    self.transferred+=chunk.len() as u64;
+                    if let Some(progress)=self.progress.as_mut() {
+                        progress(self.transferred, self.total);
+                        }
+
+                    return Ok(Some((chunk, self)));
+                    }
+
+                let (_, hasher)=self.current_part.take().unwrap();
+                if let Some(hasher)=hasher {
+                    let part_number=self.next_part-1;
+                    if format!("{:x}", hasher.finalize())!=self.parts[part_number].sha256 {
+                        anyhow::bail!("Part {part_number} of the shared clipboard is corrupted.");
+                        }
+                    }
+                }
+
+            if self.next_part>=self.parts.len() {
+                return Ok(None);
+                }
+
+            let res=self.client.get(format!("{}/{}", self.parts_url, self.next_part))
+            .header(header::ACCEPT, OCTET_STREAM)
+            .send().await.context(ClipshareError::Network)?;
+
+            if !res.status().is_success() {
+                return Err(ClipshareError::from_response(res).await.into());
+                }
+
+            self.current_part=Some((res, Some(Sha256::new())));
+            self.next_part+=1;
+            }
+        }
+    }
+
+/// Capabilities and limits of a server, as reported by its info endpoint.
+#[derive(Clone, Deserialize, Getters)]
+pub struct ServerInfo {
+    /// The version of the server software.
+    version: String,
+    api_version: u32,
+    /// The maximum size of a single-part clipboard or a single part, in bytes.
+    max_content_size: u64,
+    /// The maximum total size of a clipboard, in bytes.
+    max_clipboard_size: u64,
+    features: Vec<String>,
+    }
+impl ServerInfo {
+
+    pub fn supports(&self, feature: &str) -> bool {
+        self.features.iter().any(|supported_feature| supported_feature==feature)
+        }
+    }
+
+/// What the client knows about the API of a server.
+#[derive(Clone)]
+enum ServerApi {
+    /// The server could not be asked yet.
+    Unknown,
+    /// The server predates the versioned API, only the unversioned routes are available.
+    Legacy,
+    V1(ServerInfo),
+    }
+
+/// A wrapper structure for communication with a clipshare server instance.
+/// All requests go through a single HTTP client, reusing its connections.
+pub struct Clipshare {
+    host: String,
+    client: reqwest::Client,
+    retry_policy: RetryPolicy,
+    server_api: Mutex<ServerApi>,
+    }
+impl Clipshare {
+
+    /// Creates a new instance of Clipshare with the default network settings.
+    pub fn new(host: &str) -> Result<Clipshare, anyhow::Error> {
+        Clipshare::with_options(host, &ClientOptions::default())
+        }
+
+    /// Creates a new instance of Clipshare with the given network settings.
+    /// The server is asked for its capabilities on the first request needing them, and again on the next one if it can't be reached.
+    pub fn with_options(host: &str, options: &ClientOptions) -> Result<Clipshare, anyhow::Error> {
+        let host=host.to_string();
+        let client=options.build_client()?;
+        let retry_policy=options.retry.clone();
+        let server_api=Mutex::new(ServerApi::Unknown);
+
+        Ok(Clipshare { host, client, retry_policy, server_api })
+        }
+
+    /// Capabilities and limits of the server, None if the server predates the versioned API or could not be reached.
+    pub async fn server_info(&self) -> Option<ServerInfo> {
+        match self.server_api().await {
+            ServerApi::V1(server_info) => Some(server_info),
+            _ => None,
+            }
+        }
+
+    /// Whether the server responds, asking it again if it could not be reached before.
+    pub async fn is_reachable(&self) -> bool {
+        !matches!(self.server_api().await, ServerApi::Unknown)
+        }
+
+    /// Resolves the host name of the server, returning its addresses.
+    pub async fn resolve_host(&self) -> Result<Vec<SocketAddr>, anyhow::Error> {
+        let url=reqwest::Url::parse(&self.host).with_context(|| format!("Invalid host {}", self.host))?;
+        let host=url.host_str().with_context(|| format!("The host {} has no host name", self.host))?;
+        let port=url.port_or_known_default().unwrap_or(443);
+
+        let addresses=tokio::net::lookup_host((host, port)).await
+        .with_context(|| format!("Unable to resolve {host}"))?
+        .collect();
+
+        Ok(addresses)
+        }
+
+    /// Checks the server can be connected to, including the TLS handshake and the validation of its certificate.
+    /// Any response counts, regardless of its status. The step that failed is told by connection_failure.
+    pub async fn check_connection(&self) -> Result<(), anyhow::Error> {
+        self.client.get(format!("{}/api/v1/info", self.host))
+        .timeout(SERVER_INFO_TIMEOUT)
+        .send().await
+        .context(ClipshareError::Network)?;
+
+        Ok(())
+        }
+
+    /// The maximum size of encrypted content the server accepts, None if not known.
+    pub async fn max_content_size(&self) -> Option<u64> {
+        let server_info=self.server_info().await?;
+
+        if server_info.supports("multipart") {
+            return Some(server_info.max_clipboard_size);
+            }
+
+        Some(server_info.max_content_size)
+        }
+
+    async fn server_api(&self) -> ServerApi {
+        let server_api=self.server_api.lock().unwrap().clone();
+        if !matches!(server_api, ServerApi::Unknown) {
+            return server_api;
+            }
+
+        let server_api=self.query_server_api().await;
+        *self.server_api.lock().unwrap()=server_api.clone();
+
+        server_api
+        }
+    async fn query_server_api(&self) -> ServerApi {
+        let res=self.client.get(format!("{}/api/v1/info", self.host))
+        .timeout(SERVER_INFO_TIMEOUT)
+        .send().await;
+
+        match res {
+            Ok(res) if res.status().is_success() => match res.json::<ServerInfo>().await {
+                Ok(server_info) => ServerApi::V1(server_info),
+                Err(_) => ServerApi::Legacy,
+                },
+            // Servers predating the versioned API don't know the route
+            Ok(res) if res.status()==StatusCode::NOT_FOUND => ServerApi::Legacy,
+            _ => ServerApi::Unknown,
+            }
+        }
+
+    /// The URL the clipboard routes are served under.
+    async fn clipboard_url(&self, clipboard_id: &str) -> String {
+        match self.server_api().await {
+            ServerApi::V1(_) => format!("{}/api/v1/clipboard/{}", self.host, clipboard_id),
+            _ => format!("{}/clipboard/{}", self.host, clipboard_id),
+            }
+        }
+
+    /// Gets the raw content of a shared clipboard as a stream, so large content can be processed while it's being downloaded.
+    pub async fn get_content(&self, clipboard_id: &str, progress: Option<Progress>) -> Result<ContentStream, anyhow::Error> {
+        let res=self.client.get(self.clipboard_url(clipboard_id).await)
+        .header(header::ACCEPT, OCTET_STREAM)
+        .send().await.context(ClipshareError::Network)?;
+
+        if !res.status().is_success() {
+            return Err(ClipshareError::from_response(res).await.into());
+            }
+
+        let content_type=res.headers().get(header::CONTENT_TYPE).cloned();
+
+        if content_type.as_ref().is_some_and(|content_type| content_type==MANIFEST_CONTENT_TYPE) {
+            let manifest: Manifest=res.json().await.context("Unable to read the manifest of the shared clipboard.")?;
+            let total=manifest.parts.iter().map(|part| part.size).sum();
+
+            let download=Download {
+                client: self.client.clone(),
+                parts_url: format!("{}/parts/{}", self.clipboard_url(clipboard_id).await, manifest.upload_id),
+                parts: manifest.parts,
+                next_part: 0,
+                current_part: None,
+                transferred: 0,
+                total: Some(total),
+                progress,
+                };
+
+            return Ok(stream::try_unfold(download, Download::next_chunk).boxed());
+            }
+
+        // Servers without binary body support respond with base64 text regardless of the Accept header
+        let binary=content_type.is_some_and(|content_type| content_type==OCTET_STREAM);
+
+        if binary {
+            let total=res.content_length();
+            let download=Download {
+                client: self.client.clone(),
+                parts_url: String::new(),
+                parts: Vec::new(),
+                next_part: 0,
+                current_part: Some((res, None)),
+                transferred: 0,
+                total,
+                progress,
+                };
+
+            return Ok(stream::try_unfold(download, Download::next_chunk).boxed());
+            }
+
+        let body=res.bytes().await.context(ClipshareError::Network)?;
+        let encrypted_content=base64_eng::STANDARD_NO_PAD.decode(&body).context("Unable to decode the shared clipboard content.")?;
+
+        Ok(stream::once(async move { Ok(Bytes::from(encrypted_content)) }).boxed())
+        }
+
+    /// Sets the raw content of a shared clipboard, streaming it from a reader of content_length bytes.
+    /// Content larger than the content size limit of the server is uploaded in parts if the server supports them, which get published at once by uploading their manifest.
+    /// Servers predating binary bodies get the content as base64 text.
+    pub async fn set_content<R: Read+Send+'static>(&self, clipboard_id: &str, content: R, content_length: u64, progress: Option<Progress>) -> Result<(), anyhow::Error> {
+        let progress=progress.map(|progress| Arc::new(Mutex::new(progress)));
+
+        if let Some(server_info)=self.server_info().await.filter(|server_info| server_info.supports("multipart")) {
+            if content_length>server_info.max_content_size {
+                return self.set_content_parts(clipboard_id, content, content_length, server_info.max_content_size, progress).await;
+                }
+            }
+        if matches!(self.server_api().await, ServerApi::Legacy) {
+            return self.set_content_base64(clipboard_id, content, progress).await;
+            }
+
+        let res=self.client.post(self.clipboard_url(clipboard_id).await)
+        .header(header::CONTENT_TYPE, OCTET_STREAM)
+        .header(header::CONTENT_LENGTH, content_length)
+        .body(progress_body(content, 0, content_length, progress))
+        .send().await.context(ClipshareError::Network)?;
+
+        if !res.status().is_success() {
+            return Err(ClipshareError::from_response(res).await.into());
+            }
+
+        Ok(())
+        }
+
+    /// Uploads content encoded as base64 text, for servers predating binary bodies.
+    /// These servers limit the clipboard size to a few megabytes, so the content is encoded in memory.
+    async fn set_content_base64(&self, clipboard_id: &str, mut content: impl Read, progress: Option<SharedProgress>) -> Result<(), anyhow::Error> {
+        let mut raw_content=Vec::new();
+        content.read_to_end(&mut raw_content).context("Unable to read the content for the shared clipboard")?;
+        let encoded_content=base64_eng::STANDARD_NO_PAD.encode(raw_content).into_bytes();
+        let encoded_length=encoded_content.len() as u64;
+
+        let res=self.client.post(self.clipboard_url(clipboard_id).await)
+        .header(header::CONTENT_LENGTH, encoded_length)
+        .body(progress_body(Cursor::new(encoded_content), 0, encoded_length, progress))
+        .send().await.context(ClipshareError::Network)?;
+
+        if !res.status().is_success() {
+            return Err(ClipshareError::from_response(res).await.into());
+            }
+
+        Ok(())
+        }
+
+    /// Uploads content in parts of part_size under a new upload ID, then publishes their manifest.
+    /// Only one part is held in memory at a time.
+    async fn set_content_parts(&self, clipboard_id: &str, mut content: impl Read, content_length: u64, part_size: u64, progress: Option<SharedProgress>) -> Result<(), anyhow::Error> {
+        let upload_id: String=rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(16)
+        .map(char::from)
+        .collect();
+        let clipboard_url=self.clipboard_url(clipboard_id).await;
+
+        let mut parts=Vec::new();
+        let mut offset=0;
+        while offset<content_length {
+            let mut part=Vec::new();
+            content.by_ref().take(part_size).read_to_end(&mut part).context("Unable to read the content for the shared clipboard")?;
+            if part.is_empty() {
+                anyhow::bail!("The content for the shared clipboard ended unexpectedly");
+                }
+
+            let part_size=part.len() as u64;
+            let sha256=format!("{:x}", Sha256::digest(&part));
+
+            let res=self.client.post(format!("{}/parts/{}/{}", clipboard_url, upload_id, parts.len()))
+            .header(header::CONTENT_TYPE, OCTET_STREAM)
+            .header(header::CONTENT_LENGTH, part_size)
+            .body(progress_body(Cursor::new(part), offset, content_length, progress.clone()))
+            .send().await.context(ClipshareError::Network)?;
+
+            if !res.status().is_success() {
+                return Err(ClipshareError::from_response(res).await.into());
+                }
+
+            parts.push(ManifestPart { size: part_size, sha256 });
+            offset+=part_size;
+            }
+
+        let res=self.client.post(format!("{clipboard_url}/manifest"))
+        .json(&Manifest { upload_id, parts })
+        .send().await.context(ClipshareError::Network)?;
+
+        if !res.status().is_success() {
+            return Err(ClipshareError::from_response(res).await.into());
+            }
+
+        Ok(())
+        }
+    }
+
+/// Turns a reader into a request body sent in pieces as they're read, reporting the progress of the transfer.
+/// The offset is the number of bytes of the transfer sent before this body.
+/// The reads run on the blocking thread pool, as reading the content may mean encrypting it.
+fn progress_body<R: Read+Send+'static>(content: R, offset: u64, total: u64, progress: Option<SharedProgress>) -> reqwest::Body {
+    let chunks=stream::unfold(Some((content, offset, progress)), move |state| async move {
+        let (content, transferred, progress)=state?;
+
+        let read=tokio::task::spawn_blocking(move || {
+            let mut content=content;
+            let mut chunk=vec![0u8; BODY_CHUNK_SIZE];
+            let read_result=content.read(&mut chunk).map(|read_count| {
+                chunk.truncate(read_count);
+                chunk
+                });
+
+            (content, read_result)
+            }).await;
+        let (content, read_result)=match read {
+            Ok(read) => read,
+            Err(e) => return Some((Err(io::Error::other(e)), None)),
+            };
+
+        match read_result {
+            Ok(chunk) if chunk.is_empty() => None,
+            Ok(chunk) => {
+                let transferred=transferred+chunk.len() as u64;
+
+                if let Some(progress)=&progress {
+                    (progress.lock().unwrap())(transferred, Some(total));
+                    }
+
+                Some((Ok(chunk), Some((content, transferred, progress))))
+                },
+            Err(e) => Some((Err(e), Some((content, transferred, progress)))),
+            }
+        });
+
+    reqwest::Body::wrap_stream(chunks)
+    }
+
+/// Runs CPU heavy work, like the key derivation and the encryption, on the blocking thread pool, so it doesn't stall the other tasks of the runtime.
+async fn run_blocking<T, F>(work: F) -> Result<T, anyhow::Error>
+where
+    T: Send+'static,
+    F: FnOnce() -> Result<T, anyhow::Error>+Send+'static,
+    {
+    tokio::task::spawn_blocking(work).await?
+    }
+
+/// A wrapper structure for working with shared clipboards.
+/// While Clipshare represents a Clipshare server instance and its functionality, SharedClipboard is a structure that represents shared clipboards as functional units.
+/// Since one Clipshare server can embrace any number of shared clipboards for the user.
+/// Although the Clipshare objects are not shared among SharedClipboards even if multiple Clipshare objects refer to the same server instance, for ergonomical reasons.
+/// SharedClipboard is the structure that gets to serialize/deserialize and encrypt/decrypt the content to be put into a shared clipboard. The Clipshare structure has only access to the resulting encrypted data and the clipboard id.
+/// Note the key derivation of the encryption is deliberately slow, taking a fraction of a second of CPU time per transfer, which is spent on the blocking thread pool.
+#[derive(Getters)]
+pub struct SharedClipboard {
+    name: String,
+    clipshare: Clipshare,
+    clipboard_id: String,
+    password: String,
+    }
+impl SharedClipboard {
+
+    /// Creates a new instance of SharedClipboard.
+    pub fn new(name: &str, clipshare: Clipshare, password: &str) -> SharedClipboard {
+        SharedClipboard {
+            name: name.to_string(),
+            clipshare,
+            clipboard_id: calculate_pseudosalted_password_hash(password),
+            password: password.to_string(),
+            }
+        }
+
+    /// Gets the content of the shared clipboard, reporting the download progress if requested.
+    /// Downloads failing on transient errors are repeated according to the retry policy of the Clipshare instance.
+    pub async fn get_content(&self, progress: Option<Progress>) -> Result<SharedClipboardContent, anyhow::Error> {
+        let progress=progress.map(|progress| Arc::new(Mutex::new(progress)));
+
+        self.clipshare.retry_policy.run(|| self.download_content(forward_progress(&progress))).await
+        }
+
+    async fn download_content(&self, progress: Option<Progress>) -> Result<SharedClipboardContent, anyhow::Error> {
+        let mut encrypted_content=self.clipshare.get_content(&self.clipboard_id, progress).await?;
+
+        let mut decryptor=Decryptor::new(&self.password);
+        while let Some(chunk)=encrypted_content.next().await {
+            let chunk=chunk?;
+
+            decryptor=run_blocking(move || {
+                decryptor.update(&chunk).context(ClipshareError::DecryptionFailed)?;
+                Ok(decryptor)
+                }).await?;
+            }
+        let serialized_content=run_blocking(move || decryptor.finish().context(ClipshareError::DecryptionFailed)).await?;
+
+        let content: SharedClipboardContent=serde_json::from_str(&serialized_content)
+        .context("Unable to deserialize the shared clipboard content.")?;
+
+        Ok(content)
+        }
+
+    /// Checks the shared clipboard can be downloaded and decrypted with its password, without downloading large content as a whole.
+    /// Content encrypted in chunks is checked by its first chunk, while content encrypted in one piece is small enough to be checked whole.
+    /// Fails like get_content, but isn't repeated on transient errors.
+    pub async fn check_content(&self) -> Result<(), anyhow::Error> {
+        let mut encrypted_content=self.clipshare.get_content(&self.clipboard_id, None).await?;
+
+        let mut decryptor=Decryptor::new(&self.password);
+        while let Some(chunk)=encrypted_content.next().await {
+            let chunk=chunk?;
+
+            decryptor=run_blocking(move || {
+                decryptor.update(&chunk).context(ClipshareError::DecryptionFailed)?;
+                Ok(decryptor)
+                }).await?;
+            if decryptor.verified() {
+                return Ok(());
+                }
+            }
+        run_blocking(move || decryptor.finish().context(ClipshareError::DecryptionFailed)).await?;
+
+        Ok(())
+        }
+
+    /// Sets the content of the shared clipboard, reporting the upload progress if requested.
+    /// Large content is encrypted in chunks while being uploaded, small content in one piece for compatibility with older clients.
+    /// Content exceeding the limits of the server is refused before the costly encryption.
+    /// Uploads failing on transient errors are repeated according to the retry policy of the Clipshare instance.
+    pub async fn set_content(&self, content: SharedClipboardContent, progress: Option<Progress>) -> Result<(), anyhow::Error> {
+        let serialized_content=serde_json::to_string(&content)
+        .context("Unable to serialize the content for the shared clipboard")?;
+
+        let streamed=serialized_content.len()>STREAM_THRESHOLD;
+        let content_length=if streamed {
+            encrypted_stream_length(serialized_content.len() as u64)
+            }
+        else {
+            encrypted_length(serialized_content.len() as u64)
+            };
+
+        if let Some(max_size)=self.clipshare.max_content_size().await {
+            if content_length>max_size {
+                return Err(ClipshareError::ContentTooLarge { size: content_length, max_size }.into());
+                }
+            }
+
+        let progress=progress.map(|progress| Arc::new(Mutex::new(progress)));
+
+        // The attempts share the content instead of copying it, Bytes are cloned by reference
+        if streamed {
+            // The chunks are encrypted while being uploaded, so every attempt encrypts the content anew
+            let serialized_content=Bytes::from(serialized_content);
+            self.clipshare.retry_policy.run(|| async {
+                let (serialized_content, password)=(serialized_content.clone(), self.password.clone());
+                let encrypted_content=run_blocking(move || encrypt_stream(Cursor::new(serialized_content), &password)).await?;
+                self.clipshare.set_content(&self.clipboard_id, encrypted_content, content_length, forward_progress(&progress)).await
+                }).await?;
+            }
+        else {
+            let password=self.password.clone();
+            let encrypted_content=Bytes::from(run_blocking(move || encrypt(&serialized_content, &password)).await?);
+            self.clipshare.retry_policy.run(|| {
+                self.clipshare.set_content(&self.clipboard_id, Cursor::new(encrypted_content.clone()), content_length, forward_progress(&progress))
+                }).await?;
+            }
+
+        Ok(())
+        }
+
+    /// Encrypts content with the password of the shared clipboard, for keeping it elsewhere, like in a queue of content waiting for upload.
+    /// Blocks for the key derivation, asynchronous callers should run it on a blocking thread.
+    pub fn seal(&self, content: &SharedClipboardContent) -> Result<Vec<u8>, anyhow::Error> {
+        let serialized_content=serde_json::to_string(content)
+        .context("Unable to serialize the content for the shared clipboard")?;
+
+        encrypt(&serialized_content, &self.password)
+        }
+
+    /// Decrypts content encrypted by seal, blocking like seal.
+    pub fn unseal(&self, sealed_content: &[u8]) -> Result<SharedClipboardContent, anyhow::Error> {
+        let serialized_content=decrypt(sealed_content, &self.password).context("Unable to decrypt the sealed content.")?;
+
+        let content: SharedClipboardContent=serde_json::from_str(&serialized_content)
+        .context("Unable to deserialize the sealed content.")?;
+
+        Ok(content)
+        }
+    }
+
+/// Passes the progress of a single attempt of a transfer to the callback shared by all attempts.
+fn forward_progress(progress: &Option<SharedProgress>) -> Option<Progress> {
+    let progress=progress.clone()?;
+
+    Some(Box::new(move |transferred, total| (progress.lock().unwrap())(transferred, total)))
+    }
+
@@ -0,0 +1,24 @@
+/*
+* Copyright (C) 2023 Rastislav Kish
+*
+* This program is free software: you can redistribute it and/or modify
+* it under the terms of the GNU General Public License as published by
+* the Free Software Foundation, version 3.
+*
+* This program is distributed in the hope that it will be useful,
+* but WITHOUT ANY WARRANTY; without even the implied warranty of
+* MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+* GNU General Public License for more details.
+*
+* You should have received a copy of the GNU General Public License
+* along with this program. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use serde::{Serialize, Deserialize};
+
+/// An enum representing the content of the SharedClipboard.
+#[derive(Clone, Serialize, Deserialize)]
+pub enum SharedClipboardContent {
+    Text(String),
+    }
+
@@ -0,0 +1,370 @@
+/*
+* Copyright (C) 2023 Rastislav Kish
+*
+* This program is free software: you can redistribute it and/or modify
+* it under the terms of the GNU General Public License as published by
+* the Free Software Foundation, version 3.
+*
+* This program is distributed in the hope that it will be useful,
+* but WITHOUT ANY WARRANTY; without even the implied warranty of
+* MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+* GNU General Public License for more details.
+*
+* You should have received a copy of the GNU General Public License
+* along with this program. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use std::io::{self, Cursor, Read};
+
+use aes_gcm::{
+    aead::{Aead, KeyInit, stream::{DecryptorBE32, EncryptorBE32}},
+    Aes256Gcm,
+    };
+use pbkdf2::pbkdf2_hmac_array;
+use sha2::Sha256;
+use rand::{RngCore, SeedableRng, rngs::StdRng};
+
+use argon2::{
+    password_hash::{PasswordHasher, Salt},
+    Argon2,
+    };
+
+use anyhow::bail;
+
+/// Magic bytes opening data encrypted in chunks, telling it apart from data encrypted in one piece, which starts with a random salt.
+const STREAM_MAGIC: &[u8; 4]=b"CSS1";
+/// Size of the plain-data chunks encrypted separately in the chunked format.
+const STREAM_CHUNK_SIZE: usize=64*1024;
+const STREAM_NONCE_PREFIX_SIZE: usize=7;
+const TAG_SIZE: usize=16;
+
+/// Content larger than this should be encrypted in chunks with encrypt_stream.
+/// Smaller content is better off with encrypt, whose format is understood also by older clients.
+pub const STREAM_THRESHOLD: usize=1024*1024;
+
+/// The iterations of the key derivation, deliberately slow to resist guessing of the password.
+/// The tests only need a derivation that works, they would take minutes unoptimized with the real count.
+const KEY_DERIVATION_ITERATIONS: u32=if cfg!(test) { 1000 } else { 700000 };
+
+fn derive_key(password: &str, salt: &[u8]) -> [u8; 32] {
+    pbkdf2_hmac_array::<Sha256, 32>(password.as_bytes(), salt, KEY_DERIVATION_ITERATIONS)
+    }
+
+/// Encrypts a string with password and returns the encrypted data.
+/// The encryption algorithm is AES256GCM with PBKDF2 HMAC SHA256 key derivation function set to 700000 iterations.
+pub fn encrypt(content: &str, password: &str) -> Result<Vec<u8>, anyhow::Error> {
+    let mut rng=StdRng::from_entropy();
+    let mut salt=[0u8; 16];
+    let mut nonce=[0u8; 12];
+    rng.fill_bytes(&mut salt);
+    rng.fill_bytes(&mut nonce);
+
+    let key=derive_key(password, &salt);
+
+    let cipher=Aes256Gcm::new(&key.into());
+    let encrypted=match cipher.encrypt(&nonce.into(), content.as_bytes().as_ref()) {
+        Ok(c) => c,
+        Err(e) => bail!("Unable to decrypt data. {e}"),
+        };
+
+    let mut result: Vec<u8>=vec![0; 16+12+encrypted.len()];
+    result[0..16].clone_from_slice(&salt);
+    result[16..28].clone_from_slice(&nonce);
+    result[28..].clone_from_slice(&encrypted);
+
+    Ok(result)
+    }
+
+/// Returns the size of content of the given length once encrypted by encrypt.
+pub fn encrypted_length(content_length: u64) -> u64 {
+    16+12+content_length+TAG_SIZE as u64
+    }
+
+/// Decrypts data with password and returns the decrypted string. Note the plain-data has to be utf-8 text, otherwise the function will error out.
+/// The encryption algorithm is AES256GCM with PBKDF2 HMAC SHA256 key derivation function set to 700000 iterations.
+pub fn decrypt(content: &[u8], password: &str) -> Result<String, anyhow::Error> {
+    if content.len()<28 {
+        bail!("Unable to decrypt data. The data is too short.");
+        }
+
+    let salt=&content[0..16];
+    let nonce=&content[16..28];
+    let key=derive_key(password, salt);
+
+    let cipher=Aes256Gcm::new(&key.into());
+    let decrypted=match cipher.decrypt(nonce.into(), content[28..].as_ref()) {
+        Ok(c) => c,
+        Err(e) => bail!("Unable to decypt data. {e}"),
+        };
+
+    Ok(std::str::from_utf8(&decrypted)?.to_string())
+    }
+
+/// Encrypts data with password in chunks as they're read, so large content doesn't need to be encrypted in one piece.
+/// The format is the STREAM construction of AES256GCM with 64 KiB chunks, the key derivation is the same as in encrypt.
+/// The encrypted data consists of the magic bytes, the salt, the nonce prefix and the encrypted chunks, see encrypted_stream_length for its size.
+pub fn encrypt_stream<R: Read>(content: R, password: &str) -> Result<EncryptingReader<R>, anyhow::Error> {
+    let mut rng=StdRng::from_entropy();
+    let mut salt=[0u8; 16];
+    let mut nonce_prefix=[0u8; STREAM_NONCE_PREFIX_SIZE];
+    rng.fill_bytes(&mut salt);
+    rng.fill_bytes(&mut nonce_prefix);
+
+    let key=derive_key(password, &salt);
+    let encryptor=EncryptorBE32::from_aead(Aes256Gcm::new(&key.into()), &nonce_prefix.into());
+
+    let mut header=Vec::with_capacity(STREAM_MAGIC.len()+salt.len()+nonce_prefix.len());
+    header.extend_from_slice(STREAM_MAGIC);
+    header.extend_from_slice(&salt);
+    header.extend_from_slice(&nonce_prefix);
+
+    let mut content=content;
+    let next_chunk=read_chunk(&mut content, STREAM_CHUNK_SIZE)?;
+
+    Ok(EncryptingReader {
+        content,
+        encryptor: Some(encryptor),
+        next_chunk,
+        output: Cursor::new(header),
+        })
+    }
+
+/// Returns the size of content of the given length once encrypted by encrypt_stream.
+pub fn encrypted_stream_length(content_length: u64) -> u64 {
+    // Even empty content makes a single, empty chunk
+    let chunk_count=content_length.div_ceil(STREAM_CHUNK_SIZE as u64).max(1);
+
+    (STREAM_MAGIC.len()+16+STREAM_NONCE_PREFIX_SIZE) as u64+content_length+chunk_count*TAG_SIZE as u64
+    }
+
+/// A reader encrypting the content of another reader, created by encrypt_stream.
+pub struct EncryptingReader<R: Read> {
+    content: R,
+    encryptor: Option<EncryptorBE32<Aes256Gcm>>,
+    next_chunk: Vec<u8>,
+    output: Cursor<Vec<u8>>,
+    }
+impl<R: Read> Read for EncryptingReader<R> {
+
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let read_count=self.output.read(buf)?;
+            if read_count>0 || buf.is_empty() {
+                return Ok(read_count);
+                }
+
+            let Some(encryptor)=self.encryptor.as_mut() else {
+                return Ok(0);
+                };
+
+            // Reading a chunk ahead, as the last chunk has to be encrypted differently
+            let chunk=std::mem::take(&mut self.next_chunk);
+            self.next_chunk=read_chunk(&mut self.content, STREAM_CHUNK_SIZE)?;
+
+            let encrypted=if self.next_chunk.is_empty() {
+                self.encryptor.take().unwrap().encrypt_last(chunk.as_slice())
+                }
+            else {
+                encryptor.encrypt_next(chunk.as_slice())
+                }
+            .map_err(|e| io::Error::other(format!("Unable to encrypt data. {e}")))?;
+
+            self.output=Cursor::new(encrypted);
+            }
+        }
+    }
+
+/// Decrypts data fed to it in pieces as they arrive, i.e. while the data is being downloaded.
+/// Accepts data encrypted by both encrypt and encrypt_stream, the latter is decrypted chunk by chunk, the former once all data is there.
+pub struct Decryptor {
+    password: String,
+    buffer: Vec<u8>,
+    state: DecryptorState,
+    decrypted: Vec<u8>,
+    }
+enum DecryptorState {
+    /// Not enough data to tell the format yet.
+    Start,
+    SinglePiece,
+    Chunked(Box<DecryptorBE32<Aes256Gcm>>),
+    }
+impl Decryptor {
+
+    pub fn new(password: &str) -> Decryptor {
+        Decryptor {
+            password: password.to_string(),
+            buffer: Vec::new(),
+            state: DecryptorState::Start,
+            decrypted: Vec::new(),
+            }
+        }
+
+    /// Feeds the next piece of the encrypted data.
+    pub fn update(&mut self, data: &[u8]) -> Result<(), anyhow::Error> {
+        self.buffer.extend_from_slice(data);
+
+        if let DecryptorState::Start=self.state {
+            let header_size=STREAM_MAGIC.len()+16+STREAM_NONCE_PREFIX_SIZE;
+
+            if self.buffer.len()<STREAM_MAGIC.len() {
+                return Ok(());
+                }
+            if !self.buffer.starts_with(STREAM_MAGIC) {
+                self.state=DecryptorState::SinglePiece;
+                return Ok(());
+                }
+            if self.buffer.len()<header_size {
+                return Ok(());
+                }
+
+            let (salt, nonce_prefix)=self.buffer[STREAM_MAGIC.len()..header_size].split_at(16);
+            let key=derive_key(&self.password, salt);
+            self.state=DecryptorState::Chunked(Box::new(DecryptorBE32::from_aead(Aes256Gcm::new(&key.into()), nonce_prefix.into())));
+            self.buffer.drain(..header_size);
+            }
+
+        if let DecryptorState::Chunked(decryptor)=&mut self.state {
+            // Keeping at least one chunk in the buffer, as the last chunk has to be decrypted differently
+            while self.buffer.len()>STREAM_CHUNK_SIZE+TAG_SIZE {
+                match decryptor.decrypt_next(&self.buffer[..STREAM_CHUNK_SIZE+TAG_SIZE]) {
+                    Ok(c) => self.decrypted.extend_from_slice(&c),
+                    Err(e) => bail!("Unable to decypt data. {e}"),
+                    };
+                self.buffer.drain(..STREAM_CHUNK_SIZE+TAG_SIZE);
+                }
+            }
+
+        Ok(())
+        }
+
+    /// Whether a part of the data has been decrypted already, proving the password is right before all data is there.
+    pub fn verified(&self) -> bool {
+        !self.decrypted.is_empty()
+        }
+
+    /// Decrypts the rest of the data and returns the decrypted string, see decrypt for the requirements.
+    pub fn finish(mut self) -> Result<String, anyhow::Error> {
+        let DecryptorState::Chunked(decryptor)=self.state else {
+            return decrypt(&self.buffer, &self.password);
+            };
+
+        // A missing last chunk means the data got truncated
+        if self.buffer.is_empty() {
+            bail!("Unable to decrypt data. The data is truncated.");
+            }
+
+        match decryptor.decrypt_last(self.buffer.as_slice()) {
+            Ok(c) => self.decrypted.extend_from_slice(&c),
+            Err(e) => bail!("Unable to decypt data. {e}"),
+            };
+
+        Ok(String::from_utf8(self.decrypted)?)
+        }
+    }
+
+/// Reads up to size bytes, fewer only at the end of the data.
+fn read_chunk(content: &mut impl Read, size: usize) -> io::Result<Vec<u8>> {
+    let mut chunk=Vec::with_capacity(size);
+    content.take(size as u64).read_to_end(&mut chunk)?;
+
+    Ok(chunk)
+    }
+
+/// Calculates a string hash, returning the hash in url-safe base64 format (+ -> -, / -> _)
+/// This function uses a hard-code static salt value in order to make the hashes suitable for passwordless identification. DO NOT use for other purposes
+/// The hashing algorithm used is Argon2 id v19
+pub fn calculate_pseudosalted_password_hash(password: &str) -> String {
+    let argon2=Argon2::new(
+        argon2::Algorithm::Argon2id,
+        argon2::Version::V0x13,
+        argon2::Params::default(),
+        );
+
+    let password_hash=argon2.hash_password(password.as_bytes(), Salt::from_b64("5eVS51U/D9XXhWK37D6qAg").unwrap()).unwrap();
+    let hash=password_hash.hash.unwrap().to_string();
+
+    hash.replace('+', "-").replace('/', "_")
+    }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PASSWORD: &str="correct horse battery staple";
+
+    fn content(length: usize) -> String {
+        (0..length).map(|i| (b'a'+(i%26) as u8) as char).collect()
+        }
+
+    fn encrypt_stream_to_vec(content: &str) -> Vec<u8> {
+        let mut encrypted=Vec::new();
+        encrypt_stream(content.as_bytes(), PASSWORD).unwrap()
+        .read_to_end(&mut encrypted).unwrap();
+
+        encrypted
+        }
+
+    /// Feeds the data to a Decryptor in pieces not aligned to the chunks, like they'd arrive from the network.
+    fn decrypt_in_pieces(data: &[u8], password: &str) -> Result<String, anyhow::Error> {
+        let mut decryptor=Decryptor::new(password);
+        for piece in data.chunks(1000) {
+            decryptor.update(piece)?;
+            }
+
+        decryptor.finish()
+        }
+
+    #[test]
+    fn round_trips_streams_at_chunk_boundaries() {
+        for length in [0, 1, STREAM_CHUNK_SIZE, 2*STREAM_CHUNK_SIZE] {
+            let content=content(length);
+            let encrypted=encrypt_stream_to_vec(&content);
+
+            assert_eq!(encrypted.len() as u64, encrypted_stream_length(length as u64), "length of {length} bytes");
+            assert_eq!(decrypt_in_pieces(&encrypted, PASSWORD).unwrap(), content, "round trip of {length} bytes");
+            }
+        }
+
+    #[test]
+    fn rejects_stream_truncated_at_chunk_boundary() {
+        let encrypted=encrypt_stream_to_vec(&content(2*STREAM_CHUNK_SIZE));
+        let header_size=STREAM_MAGIC.len()+16+STREAM_NONCE_PREFIX_SIZE;
+
+        // The first chunk alone decrypts fine, only the missing last chunk flag gives the truncation away
+        let truncated=&encrypted[..header_size+STREAM_CHUNK_SIZE+TAG_SIZE];
+        assert!(decrypt_in_pieces(truncated, PASSWORD).is_err());
+        assert!(decrypt_in_pieces(&encrypted[..header_size], PASSWORD).is_err());
+        }
+
+    #[test]
+    fn decrypts_single_piece_data_through_decryptor() {
+        let content=content(5000);
+        let encrypted=encrypt(&content, PASSWORD).unwrap();
+
+        assert_eq!(encrypted.len() as u64, encrypted_length(content.len() as u64));
+        assert_eq!(decrypt_in_pieces(&encrypted, PASSWORD).unwrap(), content);
+        assert_eq!(decrypt(&encrypted, PASSWORD).unwrap(), content);
+        }
+
+    #[test]
+    fn rejects_wrong_password() {
+        assert!(decrypt_in_pieces(&encrypt_stream_to_vec("content"), "wrong password").is_err());
+        assert!(decrypt_in_pieces(&encrypt("content", PASSWORD).unwrap(), "wrong password").is_err());
+        }
+
+    #[test]
+    fn verifies_the_password_by_the_first_chunk() {
+        let encrypted=encrypt_stream_to_vec(&content(2*STREAM_CHUNK_SIZE));
+        let header_size=STREAM_MAGIC.len()+16+STREAM_NONCE_PREFIX_SIZE;
+
+        let mut decryptor=Decryptor::new(PASSWORD);
+        decryptor.update(&encrypted[..header_size+STREAM_CHUNK_SIZE+TAG_SIZE]).unwrap();
+        assert!(!decryptor.verified());
+        decryptor.update(&encrypted[header_size+STREAM_CHUNK_SIZE+TAG_SIZE..][..1]).unwrap();
+        assert!(decryptor.verified());
+
+        let mut decryptor=Decryptor::new("wrong password");
+        assert!(decryptor.update(&encrypted[..header_size+STREAM_CHUNK_SIZE+TAG_SIZE+1]).is_err());
+        }
+    }
+
@@ -0,0 +1,257 @@
+/*
+* Copyright (C) 2023 Rastislav Kish
+*
+* This program is free software: you can redistribute it and/or modify
+* it under the terms of the GNU General Public License as published by
+* the Free Software Foundation, version 3.
+*
+* This program is distributed in the hope that it will be useful,
+* but WITHOUT ANY WARRANTY; without even the implied warranty of
+* MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+* GNU General Public License for more details.
+*
+* You should have received a copy of the GNU General Public License
+* along with this program. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::time::Duration;
+
+use reqwest::header;
+use reqwest::StatusCode;
+use serde::Deserialize;
+
+/// An error reported by a clipshare server, recognized from the error code of its response, or a failure of communicating with it.
+/// Returned wrapped in anyhow::Error, from which it can be recovered by downcasting.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ClipshareError {
+    InvalidId,
+    InvalidContent,
+    Unauthorized,
+    TooLarge,
+    StorageFull,
+    QuotaExceeded,
+    /// Too many requests were made, the server may tell how long to wait before the next one.
+    RateLimited { retry_after: Option<Duration> },
+    NotFound,
+    BackendUnavailable,
+    /// The server could not be reached or the connection to it failed, the underlying error is the source of this one.
+    Network,
+    /// The content of the shared clipboard could not be decrypted with its password, as it's corrupted or not encrypted by Clipshare.
+    DecryptionFailed,
+    /// Content refused by the client before uploading, as it exceeds the limits reported by the server.
+    ContentTooLarge { size: u64, max_size: u64 },
+    /// An error without a known code, carrying the message of the server.
+    Other(String),
+    }
+impl ClipshareError {
+
+    fn from_code(code: &str, message: &str, retry_after: Option<Duration>) -> ClipshareError {
+        match code {
+            "invalid_id" => ClipshareError::InvalidId,
+            "invalid_content" => ClipshareError::InvalidContent,
+            "unauthorized" => ClipshareError::Unauthorized,
+            "too_large" => ClipshareError::TooLarge,
+            "storage_full" => ClipshareError::StorageFull,
+            "quota_exceeded" => ClipshareError::QuotaExceeded,
+            "rate_limited" => ClipshareError::RateLimited { retry_after },
+            "not_found" => ClipshareError::NotFound,
+            "backend_unavailable" => ClipshareError::BackendUnavailable,
+            _ => ClipshareError::Other(message.to_string()),
+            }
+        }
+
+    /// Recognizes the error of an unsuccessful response.
+    /// Servers predating the structured errors respond with a plain message, in which case the status code is used.
+    pub(crate) async fn from_response(res: reqwest::Response) -> ClipshareError {
+        let status=res.status();
+        // Only the delay in seconds is supported, servers don't send an HTTP date
+        let retry_after=res.headers().get(header::RETRY_AFTER)
+        .and_then(|retry_after| retry_after.to_str().ok()?.trim().parse().ok())
+        .map(Duration::from_secs);
+        let body=res.text().await.unwrap_or_default();
+
+        if let Ok(error_response)=serde_json::from_str::<ErrorResponse>(&body) {
+            return ClipshareError::from_code(&error_response.error.code, &error_response.error.message, retry_after);
+            }
+
+        match status {
+            StatusCode::UNAUTHORIZED => ClipshareError::Unauthorized,
+            StatusCode::NOT_FOUND => ClipshareError::NotFound,
+            StatusCode::PAYLOAD_TOO_LARGE => ClipshareError::TooLarge,
+            StatusCode::TOO_MANY_REQUESTS if body=="Storage full" => ClipshareError::StorageFull,
+            StatusCode::TOO_MANY_REQUESTS => ClipshareError::RateLimited { retry_after },
+            StatusCode::SERVICE_UNAVAILABLE => ClipshareError::BackendUnavailable,
+            _ => ClipshareError::Other(body),
+            }
+        }
+
+    /// Tells whether the error is likely to go away on its own, so the request is worth repeating later.
+    pub fn is_transient(&self) -> bool {
+        matches!(self, ClipshareError::RateLimited { .. } | ClipshareError::BackendUnavailable)
+        }
+    }
+impl fmt::Display for ClipshareError {
+
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ClipshareError::InvalidId => write!(f, "The server rejected the clipboard ID."),
+            ClipshareError::InvalidContent => write!(f, "The server rejected the clipboard content."),
+            ClipshareError::Unauthorized => write!(f, "The clipboard is not authorized on this server."),
+            ClipshareError::TooLarge => write!(f, "The content is too large for the shared clipboard."),
+            ClipshareError::StorageFull => write!(f, "The server is full, try again later."),
+            ClipshareError::QuotaExceeded => write!(f, "The clipboard has used up its quota on the server, try again later."),
+            ClipshareError::RateLimited { .. } => write!(f, "Too many requests to the server, try again later."),
+            ClipshareError::NotFound => write!(f, "The shared clipboard is empty."),
+            ClipshareError::BackendUnavailable => write!(f, "The server storage is temporarily unavailable, try again later."),
+            ClipshareError::Network => write!(f, "Unable to connect to the shared clipboard."),
+            ClipshareError::DecryptionFailed => write!(f, "Unable to decrypt the shared clipboard."),
+            ClipshareError::ContentTooLarge { size, max_size } => write!(f, "The content is too large for the shared clipboard, it takes {size} bytes encrypted while the server accepts at most {max_size} bytes."),
+            ClipshareError::Other(message) => write!(f, "The server responded with an error. {message}"),
+            }
+        }
+    }
+impl std::error::Error for ClipshareError {}
+
+/// The step at which connecting to a server failed, recognized by connection_failure.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionFailure {
+    /// The TCP connection to the server, or to the proxy if one is used, could not be opened.
+    Connect,
+    /// The TLS handshake failed for another reason than the certificate, like the server not speaking TLS on its port.
+    Tls,
+    /// The certificate of the server is not valid, like being expired, issued for another host or by an untrusted authority.
+    Certificate,
+    /// The server didn't respond in time.
+    Timeout,
+    /// The connection failed for a reason not exposed by the underlying errors, like a proxy refusing to tunnel it or the host name not resolving.
+    Other,
+    }
+
+/// Tells at which step connecting to a server failed, None if the error isn't a failed connection.
+pub fn connection_failure(error: &anyhow::Error) -> Option<ConnectionFailure> {
+    let request_error=error.chain().find_map(|cause| cause.downcast_ref::<reqwest::Error>())?;
+
+    if request_error.is_timeout() {
+        return Some(ConnectionFailure::Timeout);
+        }
+    if !request_error.is_connect() {
+        return None;
+        }
+
+    Some(classify_connection_failure(error.chain()))
+    }
+
+/// Tells the step at which a connection failed by the typed errors among its causes.
+/// The TLS backends wrap their errors in I/O errors, possibly several times, so these are unwrapped on the way.
+fn classify_connection_failure<'a>(causes: impl Iterator<Item=&'a (dyn Error+'static)>) -> ConnectionFailure {
+    let mut failure=ConnectionFailure::Other;
+
+    for cause in causes {
+        let mut cause=Some(cause);
+
+        while let Some(current)=cause {
+            if let Some(e)=current.downcast_ref::<rustls::Error>() {
+                return match e {
+                    rustls::Error::InvalidCertificate(_) => ConnectionFailure::Certificate,
+                    _ => ConnectionFailure::Tls,
+                    };
+                }
+            if is_openssl_certificate_error(current) {
+                return ConnectionFailure::Certificate;
+                }
+            // The error of OpenSSL telling about the certificate may follow
+            if current.is::<native_tls::Error>() {
+                failure=ConnectionFailure::Tls;
+                }
+
+            let Some(e)=current.downcast_ref::<io::Error>() else {
+                break;
+                };
+            match e.kind() {
+                io::ErrorKind::TimedOut => return ConnectionFailure::Timeout,
+                io::ErrorKind::ConnectionRefused
+                | io::ErrorKind::ConnectionReset
+                | io::ErrorKind::ConnectionAborted
+                | io::ErrorKind::HostUnreachable
+                | io::ErrorKind::NetworkUnreachable
+                | io::ErrorKind::AddrNotAvailable if failure==ConnectionFailure::Other => return ConnectionFailure::Connect,
+                _ => {},
+                };
+
+            cause=e.get_ref().map(|inner| inner as &(dyn Error+'static));
+            }
+        }
+
+    failure
+    }
+
+/// Whether an error is a failed validation of a certificate by OpenSSL, the backend of native-tls on Linux and other Unix-like systems.
+#[cfg(not(any(target_os="windows", target_vendor="apple")))]
+fn is_openssl_certificate_error(error: &(dyn Error+'static)) -> bool {
+    // SSL_R_CERTIFICATE_VERIFY_FAILED, not exported by openssl-sys
+    const CERTIFICATE_VERIFY_FAILED: i32=134;
+
+    error.downcast_ref::<openssl::ssl::Error>()
+    .and_then(|e| e.ssl_error())
+    .is_some_and(|error_stack| error_stack.errors().iter().any(|e| e.reason_code()==CERTIFICATE_VERIFY_FAILED))
+    }
+#[cfg(any(target_os="windows", target_vendor="apple"))]
+fn is_openssl_certificate_error(_error: &(dyn Error+'static)) -> bool {
+    false
+    }
+
+#[derive(Deserialize)]
+struct ErrorBody {
+    code: String,
+    message: String,
+    }
+
+#[derive(Deserialize)]
+struct ErrorResponse {
+    error: ErrorBody,
+    }
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Classifies a connection failure caused by the given error.
+    fn classify(error: impl Error+Send+Sync+'static) -> ConnectionFailure {
+        let error=anyhow::Error::new(error).context(ClipshareError::Network);
+
+        classify_connection_failure(error.chain())
+        }
+
+    #[test]
+    fn recognizes_certificate_errors_wrapped_in_io_errors() {
+        let certificate_error=rustls::Error::InvalidCertificate(rustls::CertificateError::UnknownIssuer);
+        let error=io::Error::other(io::Error::new(io::ErrorKind::InvalidData, certificate_error));
+
+        assert_eq!(classify(error), ConnectionFailure::Certificate);
+        }
+
+    #[test]
+    fn recognizes_failed_tls_handshakes() {
+        let handshake_error=rustls::Error::InvalidMessage(rustls::InvalidMessage::InvalidContentType);
+        let error=io::Error::other(io::Error::new(io::ErrorKind::InvalidData, handshake_error));
+
+        assert_eq!(classify(error), ConnectionFailure::Tls);
+        }
+
+    #[test]
+    fn recognizes_failed_tcp_connections() {
+        assert_eq!(classify(io::Error::from(io::ErrorKind::ConnectionRefused)), ConnectionFailure::Connect);
+        assert_eq!(classify(io::Error::from(io::ErrorKind::TimedOut)), ConnectionFailure::Timeout);
+        assert_eq!(classify(io::Error::other("tunnel error: unsuccessful")), ConnectionFailure::Other);
+        }
+
+    #[test]
+    fn ignores_errors_other_than_failed_connections() {
+        assert_eq!(connection_failure(&anyhow::anyhow!(ClipshareError::NotFound)), None);
+        }
+    }
+
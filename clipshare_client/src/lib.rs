@@ -0,0 +1,38 @@
+/*
+* Copyright (C) 2023 Rastislav Kish
+*
+* This program is free software: you can redistribute it and/or modify
+* it under the terms of the GNU General Public License as published by
+* the Free Software Foundation, version 3.
+*
+* This program is distributed in the hope that it will be useful,
+* but WITHOUT ANY WARRANTY; without even the implied warranty of
+* MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+* GNU General Public License for more details.
+*
+* You should have received a copy of the GNU General Public License
+* along with this program. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Client library for Clipshare, end to end encrypted shared clipboards.
+//!
+//! SharedClipboard is the main entry point, encrypting the content with the clipboard password and transferring it through a Clipshare server.
+//! The API is asynchronous and needs a tokio runtime, the blocking feature adds a synchronous wrapper in the blocking module.
+//! The encryption runs on the blocking thread pool of the runtime, the functions of the cryptography module block their caller.
+
+mod client;
+mod content;
+pub mod cryptography;
+mod error;
+mod options;
+mod retry;
+
+#[cfg(feature="blocking")]
+pub mod blocking;
+
+pub use crate::client::{Clipshare, ContentStream, Progress, ServerInfo, SharedClipboard};
+pub use crate::content::SharedClipboardContent;
+pub use crate::error::{connection_failure, ClipshareError, ConnectionFailure};
+pub use crate::options::ClientOptions;
+pub use crate::retry::{is_transient, RetryPolicy};
+
@@ -0,0 +1,60 @@
+/*
+* Copyright (C) 2023 Rastislav Kish
+*
+* This program is free software: you can redistribute it and/or modify
+* it under the terms of the GNU General Public License as published by
+* the Free Software Foundation, version 3.
+*
+* This program is distributed in the hope that it will be useful,
+* but WITHOUT ANY WARRANTY; without even the implied warranty of
+* MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+* GNU General Public License for more details.
+*
+* You should have received a copy of the GNU General Public License
+* along with this program. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use std::time::Duration;
+
+use anyhow::Context;
+
+use crate::retry::RetryPolicy;
+
+/// Network settings of the HTTP client a Clipshare instance communicates with its server through.
+#[derive(Clone, Default)]
+pub struct ClientOptions {
+    /// The URL of a proxy to send all requests through. If not set, the proxy environment variables are respected.
+    pub proxy: Option<String>,
+    /// How long to wait for a connection to the server or for the next data from it, rather than for whole transfers which may take long for large content.
+    pub timeout: Option<Duration>,
+    /// A PEM encoded certificate of a certificate authority to trust in addition to the built-in ones, for servers with certificates issued by private CAs.
+    pub ca_certificate: Option<Vec<u8>>,
+    /// How transfers failing on transient errors are repeated.
+    pub retry: RetryPolicy,
+    }
+impl ClientOptions {
+
+    pub(crate) fn build_client(&self) -> Result<reqwest::Client, anyhow::Error> {
+        let mut builder=reqwest::Client::builder()
+        .https_only(true);
+
+        if let Some(proxy)=&self.proxy {
+            let proxy=reqwest::Proxy::all(proxy).with_context(|| format!("Invalid proxy {proxy}"))?;
+            builder=builder.proxy(proxy);
+            }
+        if let Some(timeout)=self.timeout {
+            builder=builder
+            .connect_timeout(timeout)
+            .read_timeout(timeout);
+            }
+        if let Some(ca_certificate)=&self.ca_certificate {
+            let ca_certificate=reqwest::Certificate::from_pem(ca_certificate).context("Invalid CA certificate")?;
+            builder=builder.add_root_certificate(ca_certificate);
+            }
+
+        let client=builder.build().context("Unable to create the HTTP client")?;
+
+        Ok(client)
+        }
+    }
+
@@ -0,0 +1,122 @@
+/*
+* Copyright (C) 2023 Rastislav Kish
+*
+* This program is free software: you can redistribute it and/or modify
+* it under the terms of the GNU General Public License as published by
+* the Free Software Foundation, version 3.
+*
+* This program is distributed in the hope that it will be useful,
+* but WITHOUT ANY WARRANTY; without even the implied warranty of
+* MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+* GNU General Public License for more details.
+*
+* You should have received a copy of the GNU General Public License
+* along with this program. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use std::future::Future;
+use std::time::Duration;
+
+use crate::error::ClipshareError;
+
+/// How failed requests are repeated, waiting twice as long before every further attempt.
+/// Only transient errors are retried, see is_transient.
+/// A rate limited request is repeated after the delay requested by the server instead, unless it's longer than max_delay.
+#[derive(Clone)]
+pub struct RetryPolicy {
+    /// The number of attempts including the first one, 1 disables retrying.
+    pub attempts: u32,
+    /// The delay before the second attempt.
+    pub initial_delay: Duration,
+    pub max_delay: Duration,
+    }
+impl RetryPolicy {
+
+    pub(crate) async fn run<T, F, Fut>(&self, mut operation: F) -> Result<T, anyhow::Error>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output=Result<T, anyhow::Error>>,
+        {
+        let mut delay=self.initial_delay;
+        let mut attempt=1;
+
+        loop {
+            match operation().await {
+                Err(e) if attempt<self.attempts && is_transient(&e) => {
+                    match retry_after(&e) {
+                        Some(retry_after) if retry_after>self.max_delay => return Err(e),
+                        Some(retry_after) => tokio::time::sleep(retry_after).await,
+                        None => tokio::time::sleep(delay).await,
+                        };
+
+                    delay=(delay*2).min(self.max_delay);
+                    attempt+=1;
+                    },
+                result => return result,
+                }
+            }
+        }
+    }
+impl Default for RetryPolicy {
+
+    fn default() -> RetryPolicy {
+        RetryPolicy {
+            attempts: 3,
+            initial_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(8),
+            }
+        }
+    }
+
+/// Tells whether an error is likely to go away on its own, like a dropped connection or an overloaded server, so the failed operation is worth repeating later.
+pub fn is_transient(error: &anyhow::Error) -> bool {
+    error.chain().any(|cause| {
+        if let Some(e)=cause.downcast_ref::<reqwest::Error>() {
+            return e.is_connect() || e.is_timeout() || e.is_request() || e.is_body();
+            }
+        if let Some(e)=cause.downcast_ref::<ClipshareError>() {
+            return e.is_transient();
+            }
+
+        false
+        })
+    }
+
+/// The delay before repeating a request, as requested by the server which rate limited it.
+fn retry_after(error: &anyhow::Error) -> Option<Duration> {
+    error.chain().find_map(|cause| match cause.downcast_ref::<ClipshareError>() {
+        Some(ClipshareError::RateLimited { retry_after }) => *retry_after,
+        _ => None,
+        })
+    }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    /// Runs an operation failing with a rate limit error under the default retry policy, returning the number of attempts.
+    fn rate_limited_attempts(retry_after: Duration) -> u32 {
+        let runtime=tokio::runtime::Builder::new_current_thread().enable_time().build().unwrap();
+        let attempts=Cell::new(0);
+
+        let result: Result<(), anyhow::Error>=runtime.block_on(RetryPolicy::default().run(|| {
+            attempts.set(attempts.get()+1);
+            async move { Err(ClipshareError::RateLimited { retry_after: Some(retry_after) }.into()) }
+            }));
+        assert!(result.is_err());
+
+        attempts.get()
+        }
+
+    #[test]
+    fn waits_as_long_as_the_server_requests() {
+        assert_eq!(rate_limited_attempts(Duration::ZERO), 3);
+        }
+
+    #[test]
+    fn gives_up_when_the_server_requests_a_long_wait() {
+        assert_eq!(rate_limited_attempts(Duration::from_secs(3600)), 1);
+        }
+    }
+
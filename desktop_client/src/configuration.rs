@@ -15,13 +15,30 @@
 */
 
 use std::collections::HashMap;
-use std::path::PathBuf;
-use std::fs;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::time::Duration;
 
 use anyhow::Context;
+use clipshare_client::ClientOptions;
 use derive_getters::Getters;
 use serde::{Serialize, Deserialize};
 
+use crate::password::{self, DEFAULT_PASSWORD};
+
+/// The server used by clipboards without configuration and offered by the init command, open to everyone.
+pub const PUBLIC_HOST: &str="https://clipshare.rastislavkish.com:3127";
+
+/// Whether the host is the public server, regardless of its port, which older configurations leave out.
+fn is_public_host(host: &str) -> bool {
+    let (public_host, _port)=PUBLIC_HOST.rsplit_once(':').unwrap();
+
+    host.strip_prefix(public_host)
+    .is_some_and(|rest| rest.is_empty() || rest.starts_with([':', '/']))
+    }
+
 #[derive(Serialize, Deserialize, Getters)]
 #[serde(default)]
 pub struct Config {
@@ -30,30 +47,31 @@ pub struct Config {
     }
 impl Config {
 
+    pub fn new(default_clipboard: &str, clipboards: HashMap<String, ClipboardConfig>) -> Config {
+        Config {
+            default_clipboard: default_clipboard.to_string(),
+            clipboards,
+            }
+        }
+
     pub fn from_toml(content: &str) -> Result<Config, anyhow::Error> {
         let config=toml::from_str(content).context("Unable to parse the configuration")?;
 
         Ok(config)
         }
     pub fn load() -> Result<Config, anyhow::Error> {
-        let mut local_config_path=std::env::current_exe().unwrap();
-        local_config_path.pop();
-        local_config_path.push("config.toml");
-
-        let mut user_level_config_path=dirs::config_dir().unwrap();
-        user_level_config_path.extend(&["clipshare", "config.toml"]);
-
         let config_paths: Vec<PathBuf>=[
-            local_config_path,
-            user_level_config_path,
+            Config::local_path(),
+            Config::user_level_path(),
             ]
         .into_iter()
+        .flatten()
         .filter(|path| path.exists() && path.is_file())
         .collect();
 
         if let Some(path)=config_paths.into_iter().next() {
-            let content=fs::read_to_string(&path).with_context(|| format!("Unable to read config from {path:?}"))?;
-            let config=Config::from_toml(&content).with_context(|| format!("Unable to parse the config from {path:?}"))?;
+            let content=fs::read_to_string(&path).with_context(|| ConfigError::Unreadable(path.clone()))?;
+            let config=Config::from_toml(&content).with_context(|| ConfigError::Invalid(path.clone()))?;
 
             return Ok(config);
             }
@@ -61,6 +79,64 @@ impl Config {
         Ok(Config::default())
         }
 
+    /// Warnings about the clipboards with weak passwords, sorted by their names.
+    pub fn password_warnings(&self) -> Vec<String> {
+        let mut names: Vec<&String>=self.clipboards.keys().collect();
+        names.sort();
+
+        names.into_iter()
+        .filter_map(|name| {
+            let configuration=&self.clipboards[name];
+            let weakness=configuration.password_weakness()?;
+
+            let copying=if configuration.allow_weak_password { "allowed by allow_weak_password" } else { "refused" };
+            Some(format!("The password of {name} is weak as {weakness}, copying to it is {copying}."))
+            })
+        .collect()
+        }
+
+    /// The path of the configuration next to the executable, taking precedence over the one in the system's config dir.
+    pub fn local_path() -> Option<PathBuf> {
+        let mut path=std::env::current_exe().ok()?;
+        path.pop();
+        path.push("config.toml");
+
+        Some(path)
+        }
+
+    /// The path of the configuration in the system's config dir, used unless there's a configuration next to the executable.
+    pub fn user_level_path() -> Option<PathBuf> {
+        let mut path=dirs::config_dir()?;
+        path.extend(&["clipshare", "config.toml"]);
+
+        Some(path)
+        }
+
+    /// Writes the configuration to path, readable only by its owner where supported, as it contains the passwords of the clipboards.
+    pub fn save(&self, path: &Path) -> Result<(), anyhow::Error> {
+        let content=toml::to_string(self).context("Unable to serialize the configuration")?;
+
+        if let Some(directory)=path.parent() {
+            fs::create_dir_all(directory).with_context(|| format!("Unable to create the config directory {directory:?}"))?;
+            }
+
+        let mut options=OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+        let mut file=options.open(path).with_context(|| format!("Unable to write config to {path:?}"))?;
+        // The mode applies only to newly created files, an existing one is restricted before the passwords get in
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            file.set_permissions(fs::Permissions::from_mode(0o600)).with_context(|| format!("Unable to restrict the permissions of {path:?}"))?;
+            }
+
+        file.write_all(content.as_bytes()).with_context(|| format!("Unable to write config to {path:?}"))?;
+
+        Ok(())
+        }
     }
 impl Default for Config {
 
@@ -75,7 +151,7 @@ impl Default for Config {
         }
     }
 
-#[derive(Serialize, Deserialize, Getters)]
+#[derive(Clone, Serialize, Deserialize, Getters)]
 #[serde(default)]
 pub struct ClipboardConfig {
     host: String,
@@ -84,18 +160,103 @@ pub struct ClipboardConfig {
     paste_hotkey: String,
     sync_copy_hotkey: String,
     sync_paste_hotkey: String,
+    /// The URL of a proxy to reach the host through, empty to respect the proxy environment variables.
+    proxy: String,
+    /// How long to wait for the host in seconds, 0 to wait indefinitely.
+    timeout: u64,
+    /// The path to a PEM certificate of a private certificate authority the host's certificate is issued by, empty for none.
+    ca_certificate: String,
+    /// Allows copying to the clipboard even with a weak password.
+    allow_weak_password: bool,
+    }
+impl ClipboardConfig {
+
+    /// Creates the configuration of a clipboard with the default network settings.
+    pub fn new(host: &str, password: &str, copy_hotkey: &str, paste_hotkey: &str, sync_copy_hotkey: &str, sync_paste_hotkey: &str) -> ClipboardConfig {
+        ClipboardConfig {
+            host: host.to_string(),
+            password: password.to_string(),
+            copy_hotkey: copy_hotkey.to_string(),
+            paste_hotkey: paste_hotkey.to_string(),
+            sync_copy_hotkey: sync_copy_hotkey.to_string(),
+            sync_paste_hotkey: sync_paste_hotkey.to_string(),
+            ..ClipboardConfig::default()
+            }
+        }
+
+    /// Tells why the password of the clipboard is weak, None if it's strong enough.
+    pub fn password_weakness(&self) -> Option<String> {
+        let weakness=password::weakness(&self.password)?;
+
+        if is_public_host(&self.host) {
+            return Some(format!("{weakness}, and anyone can try it on the public server"));
+            }
+
+        Some(weakness)
+        }
+
+    /// Checks the clipboard can be copied to, refusing weak passwords unless explicitly allowed, as the copied content could be read by others.
+    pub fn check_password(&self, name: &str) -> Result<(), ConfigError> {
+        match self.password_weakness() {
+            Some(weakness) if !self.allow_weak_password => Err(ConfigError::WeakPassword(name.to_string(), weakness)),
+            _ => Ok(()),
+            }
+        }
+
+    /// The network settings for communication with the host.
+    pub fn client_options(&self) -> Result<ClientOptions, anyhow::Error> {
+        let proxy=Some(self.proxy.clone()).filter(|proxy| !proxy.is_empty());
+        let timeout=Some(Duration::from_secs(self.timeout)).filter(|timeout| !timeout.is_zero());
+        let ca_certificate=if !self.ca_certificate.is_empty() {
+            let ca_certificate=fs::read(&self.ca_certificate).with_context(|| ConfigError::UnreadableCaCertificate(self.ca_certificate.clone()))?;
+            Some(ca_certificate)
+            }
+        else {
+            None
+            };
+
+        Ok(ClientOptions { proxy, timeout, ca_certificate, ..ClientOptions::default() })
+        }
     }
 impl Default for ClipboardConfig {
 
     fn default() -> ClipboardConfig {
         ClipboardConfig {
-            host: String::from("https://clipshare.rastislavkish.com"),
-            password: String::from("DefaultPassword"),
+            host: String::from(PUBLIC_HOST),
+            password: String::from(DEFAULT_PASSWORD),
             copy_hotkey: String::new(),
             paste_hotkey: String::new(),
             sync_copy_hotkey: String::new(),
             sync_paste_hotkey: String::new(),
+            proxy: String::new(),
+            timeout: 30,
+            ca_certificate: String::new(),
+            allow_weak_password: false,
+            }
+        }
+    }
+
+/// An error of the configuration, returned wrapped in anyhow::Error, from which it can be recovered by downcasting.
+#[derive(Debug)]
+pub enum ConfigError {
+    Unreadable(PathBuf),
+    Invalid(PathBuf),
+    UnknownClipboard(String),
+    UnreadableCaCertificate(String),
+    /// Copying to a clipboard refused for its weak password, with the name of the clipboard and the reason.
+    WeakPassword(String, String),
+    }
+impl fmt::Display for ConfigError {
+
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::Unreadable(path) => write!(f, "Unable to read config from {path:?}"),
+            ConfigError::Invalid(path) => write!(f, "Unable to parse the config from {path:?}"),
+            ConfigError::UnknownClipboard(name) => write!(f, "Unable to find clipboard {name}"),
+            ConfigError::UnreadableCaCertificate(path) => write!(f, "Unable to read the CA certificate from {path}"),
+            ConfigError::WeakPassword(name, weakness) => write!(f, "Refusing to copy to {name}, its password is weak as {weakness}. Change the password, for example with clipshare init, or set allow_weak_password=true for the clipboard."),
             }
         }
     }
+impl std::error::Error for ConfigError {}
 
@@ -15,27 +15,54 @@
 */
 
 use std::collections::HashMap;
+use std::io::{IsTerminal, Read, Write};
+use std::process::ExitCode;
 use std::rc::Rc;
 use std::sync::{LazyLock, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
 
 use anyhow::Context;
 use arboard::Clipboard;
-use clap::{Args, Parser, Subcommand};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use clipshare_client::{connection_failure, is_transient, ClipshareError, ConnectionFailure, Progress, SharedClipboardContent};
+use clipshare_client::blocking::{Clipshare, SharedClipboard};
+use clipshare_client::cryptography::calculate_pseudosalted_password_hash;
 use enigo::{Enigo, KeyboardControllable};
 use global_hotkey::{
     GlobalHotKeyManager, GlobalHotKeyEvent, HotKeyState,
     };
 use notify_rust::Notification;
+use serde::Serialize;
 use winit::event_loop::{ControlFlow, EventLoopBuilder};
 
 mod configuration;
-mod core;
-mod cryptography;
-
-use crate::configuration::Config;
-use crate::core::{Clipshare, SharedClipboard, SharedClipboardContent};
+mod outbox;
+mod password;
+mod wizard;
+
+use crate::configuration::{ClipboardConfig, Config, ConfigError};
+use crate::outbox::{CopyQueued, Outbox};
+
+/// Transfers smaller than this finish too quickly for their progress to be worth printing.
+const PROGRESS_THRESHOLD: u64=1024*1024;
+/// Exit codes of failed commands, telling scripts what went wrong.
+/// Failures not covered by a specific code exit with 1.
+const EXIT_CLIPBOARD_NOT_FOUND: u8=2;
+const EXIT_EMPTY_CLIPBOARD: u8=3;
+const EXIT_NETWORK_ERROR: u8=4;
+const EXIT_DECRYPTION_FAILURE: u8=5;
+const EXIT_CONFIG_ERROR: u8=6;
+const EXIT_COPY_QUEUED: u8=7;
+const EXIT_WEAK_PASSWORD: u8=8;
+
+/// How often the daemon tries to deliver copies queued while the servers were unreachable.
+const OUTBOX_DELIVERY_INTERVAL: Duration=Duration::from_secs(30);
 
 static CLIPBOARD: LazyLock<Mutex<Clipboard>> = LazyLock::new(|| Mutex::new(Clipboard::new().unwrap()));
+static OUTBOX: LazyLock<Outbox>=LazyLock::new(Outbox::new);
+/// Whether to report to the console rather than by system notifications.
+static CONSOLE_OUTPUT: AtomicBool=AtomicBool::new(false);
 
 #[derive(Parser)]
 #[command(author, version, about, long_about=None)]
@@ -43,12 +70,21 @@ static CLIPBOARD: LazyLock<Mutex<Clipboard>> = LazyLock::new(|| Mutex::new(Clipb
 struct Cli {
     #[command(subcommand)]
     command: Commands,
+    /// Reports to the console rather than by system notifications, the default without a notification service
+    #[arg(long, visible_alias="quiet", global=true)]
+    console: bool,
     }
 
 #[derive(Subcommand)]
 enum Commands {
+    /// Creates the configuration interactively
+    Init(InitArgs),
     /// Displays the shared clipboard ID
     Id(IdArgs),
+    /// Lists the configured clipboards with their IDs and the reachability of their servers
+    List(ListArgs),
+    /// Diagnoses the connection to the servers and the access to the configured clipboards
+    Status(StatusArgs),
     /// Launches the Clipbshare daemon
     Daemon(DaemonArgs),
     /// Copies content to the shared clipboard
@@ -57,11 +93,76 @@ enum Commands {
     Paste(PasteArgs),
     }
 
+#[derive(Args)]
+struct InitArgs {
+    /// Overwrites an existing configuration
+    #[arg(short, long)]
+    force: bool,
+    }
+
 #[derive(Args)]
 struct IdArgs {
     /// Sets the clipboard to use
     #[arg(short, long)]
     clipboard: Option<String>,
+    /// Prints the ID in the given format rather than showing it in a notification
+    #[arg(short, long)]
+    format: Option<OutputFormat>,
+    }
+
+#[derive(Args)]
+struct ListArgs {
+    /// Sets the output format
+    #[arg(short, long, default_value="plain")]
+    format: OutputFormat,
+    }
+
+#[derive(Args)]
+struct StatusArgs {
+    /// Diagnoses only the given clipboard
+    #[arg(short, long)]
+    clipboard: Option<String>,
+    }
+
+#[derive(Clone, Copy, ValueEnum)]
+enum OutputFormat {
+    /// Human readable text, just the value for single values
+    Plain,
+    Json,
+    }
+
+/// A configured clipboard as printed by the informational commands.
+#[derive(Serialize)]
+struct ClipboardInfo<'a> {
+    name: &'a str,
+    default: bool,
+    host: &'a str,
+    id: String,
+    #[serde(skip_serializing_if="Option::is_none")]
+    hotkeys: Option<Hotkeys<'a>>,
+    #[serde(skip_serializing_if="Option::is_none")]
+    reachable: Option<bool>,
+    }
+
+#[derive(Serialize)]
+struct Hotkeys<'a> {
+    copy: Option<&'a str>,
+    paste: Option<&'a str>,
+    sync_copy: Option<&'a str>,
+    sync_paste: Option<&'a str>,
+    }
+impl<'a> Hotkeys<'a> {
+
+    fn new(configuration: &'a ClipboardConfig) -> Hotkeys<'a> {
+        let hotkey=|hotkey: &'a String| Some(hotkey.as_str()).filter(|hotkey| !hotkey.is_empty());
+
+        Hotkeys {
+            copy: hotkey(configuration.copy_hotkey()),
+            paste: hotkey(configuration.paste_hotkey()),
+            sync_copy: hotkey(configuration.sync_copy_hotkey()),
+            sync_paste: hotkey(configuration.sync_paste_hotkey()),
+            }
+        }
     }
 
 #[derive(Args)]
@@ -74,6 +175,9 @@ struct CopyArgs {
     /// Sets the sync mode
     #[arg(short, long)]
     sync_mode: bool,
+    /// Copies the standard input rather than the selection, without needing a display
+    #[arg(long, conflicts_with="sync_mode")]
+    stdin: bool,
     /// Sets the clipboard to use
     #[arg(short, long)]
     clipboard: Option<String>,
@@ -84,35 +188,208 @@ struct PasteArgs {
     /// Sets the sync mode
     #[arg(short, long)]
     sync_mode: bool,
+    /// Writes to the standard output rather than pasting, without needing a display
+    #[arg(long, conflicts_with="sync_mode")]
+    stdout: bool,
     /// Sets the clipboard to use
     #[arg(short, long)]
     clipboard: Option<String>,
     }
 
-fn main() -> Result<(), anyhow::Error> {
+fn main() -> ExitCode {
     let cli=Cli::parse();
-    let config=Config::load()?;
 
-    match &cli.command {
-        Commands::Id(args) => id_command(args, &config),
-        Commands::Daemon(args) => daemon_command(args, &config),
-        Commands::Copy(args) => copy_command(args, &config),
-        Commands::Paste(args) => paste_command(args, &config),
+    // Piping works without a display, so it can't rely on system notifications either
+    // The wizard is interactive, its errors belong to the console it runs in
+    let console_command=match &cli.command {
+        Commands::Init(_) => true,
+        Commands::Copy(args) => args.stdin,
+        Commands::Paste(args) => args.stdout,
+        _ => false,
+        };
+    CONSOLE_OUTPUT.store(cli.console || console_command || !system_notifications_available(), Ordering::Relaxed);
+
+    // The wizard creates the configuration, so it can't depend on loading it
+    let result=match &cli.command {
+        Commands::Init(args) => init_command(args),
+        _ => Config::load().and_then(|config| {
+            warn_about_weak_passwords(&cli.command, &config);
+            run_command(&cli.command, &config)
+            }),
+        };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            let exit_code=exit_code(&e);
+            notify_err(e, true);
+            exit_code
+            },
         }
-    .unwrap_or_else(|e| notify_err(e, true));
+    }
 
-    Ok(())
+fn run_command(command: &Commands, config: &Config) -> Result<(), anyhow::Error> {
+    match command {
+        Commands::Init(args) => init_command(args),
+        Commands::Id(args) => id_command(args, config),
+        Commands::List(args) => list_command(args, config),
+        Commands::Status(args) => status_command(args, config),
+        Commands::Daemon(args) => daemon_command(args, config),
+        Commands::Copy(args) => copy_command(args, config),
+        Commands::Paste(args) => paste_command(args, config),
+        }
+    }
+
+/// Warns about clipboards with weak passwords, including the default one used without a configuration.
+/// The daemon shows the warnings as system notifications, as it usually runs without a console, status reports them among its checks.
+fn warn_about_weak_passwords(command: &Commands, config: &Config) {
+    let system_notification=match command {
+        Commands::Status(_) => return,
+        Commands::Daemon(_) => true,
+        _ => false,
+        };
+
+    for warning in config.password_warnings() {
+        if !system_notification || !show_notification(&warning) {
+            eprintln!("Warning: {warning}");
+            }
+        }
+    }
+
+/// Tells the exit code of a failed command by the kind of its error.
+fn exit_code(error: &anyhow::Error) -> ExitCode {
+    if error.is::<CopyQueued>() {
+        return ExitCode::from(EXIT_COPY_QUEUED);
+        }
+    if let Some(config_error)=error.downcast_ref::<ConfigError>() {
+        return match config_error {
+            ConfigError::UnknownClipboard(_) => ExitCode::from(EXIT_CLIPBOARD_NOT_FOUND),
+            ConfigError::WeakPassword(..) => ExitCode::from(EXIT_WEAK_PASSWORD),
+            _ => ExitCode::from(EXIT_CONFIG_ERROR),
+            };
+        }
+
+    match error.downcast_ref::<ClipshareError>() {
+        Some(ClipshareError::NotFound) => ExitCode::from(EXIT_EMPTY_CLIPBOARD),
+        Some(ClipshareError::DecryptionFailed) => ExitCode::from(EXIT_DECRYPTION_FAILURE),
+        Some(ClipshareError::Network) => ExitCode::from(EXIT_NETWORK_ERROR),
+        // Overloaded servers fail in the same way as unreachable ones, only try again later helps
+        _ if is_transient(error) => ExitCode::from(EXIT_NETWORK_ERROR),
+        _ => ExitCode::FAILURE,
+        }
     }
 
+fn init_command(args: &InitArgs) -> Result<(), anyhow::Error> {
+    let path=Config::user_level_path().context("Unable to find the config directory")?;
+    if path.exists() && !args.force {
+        anyhow::bail!("The configuration {path:?} already exists, use --force to overwrite it");
+        }
+
+    let config=wizard::create_config()?;
+    config.save(&path)?;
+    println!("The configuration was written to {path:?}");
+
+    if let Some(local_path)=Config::local_path().filter(|local_path| local_path.is_file()) {
+        println!("Warning: The configuration {local_path:?} next to the executable takes precedence, remove it to use the new one.");
+        }
+
+    Ok(())
+    }
 fn id_command(args: &IdArgs, config: &Config) -> Result<(), anyhow::Error> {
     let clipboard_name=match &args.clipboard {
         Some(c) => c.to_string(),
         None => config.default_clipboard().to_string(),
         };
 
-    let shared_clipboard=get_shared_clipboard(&clipboard_name, config)?;
+    // The ID is derived from the password, there's no need to connect to the server
+    let configuration=clipboard_configuration(&clipboard_name, config)?;
+    let clipboard_id=calculate_pseudosalted_password_hash(configuration.password());
+
+    match args.format {
+        None => notify(&format!("Clipboard {clipboard_name} id: {clipboard_id}"), true),
+        Some(OutputFormat::Plain) => println!("{clipboard_id}"),
+        Some(OutputFormat::Json) => {
+            let clipboard_info=ClipboardInfo {
+                name: &clipboard_name,
+                default: clipboard_name==*config.default_clipboard(),
+                host: configuration.host(),
+                id: clipboard_id,
+                hotkeys: None,
+                reachable: None,
+                };
+
+            println!("{}", serde_json::to_string_pretty(&clipboard_info)?);
+            },
+        }
+
+    Ok(())
+    }
+fn list_command(args: &ListArgs, config: &Config) -> Result<(), anyhow::Error> {
+    let mut clipboard_names: Vec<&String>=config.clipboards().keys().collect();
+    clipboard_names.sort();
+
+    let mut clipboard_infos=Vec::new();
+    for name in clipboard_names {
+        let configuration=&config.clipboards()[name];
+
+        let reachable=configuration.client_options()
+        .and_then(|options| Clipshare::with_options(configuration.host(), &options))
+        .is_ok_and(|clipshare| clipshare.is_reachable());
+
+        clipboard_infos.push(ClipboardInfo {
+            name,
+            default: name==config.default_clipboard(),
+            host: configuration.host(),
+            id: calculate_pseudosalted_password_hash(configuration.password()),
+            hotkeys: Some(Hotkeys::new(configuration)),
+            reachable: Some(reachable),
+            });
+        }
+
+    match args.format {
+        OutputFormat::Plain => {
+            for clipboard_info in &clipboard_infos {
+                print_clipboard_info(clipboard_info);
+                }
+            },
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&clipboard_infos)?),
+        }
 
-    notify(&format!("Clipboard {} id: {}", clipboard_name, shared_clipboard.clipboard_id()), true);
+    Ok(())
+    }
+fn status_command(args: &StatusArgs, config: &Config) -> Result<(), anyhow::Error> {
+    let clipboard_names: Vec<&String>=match &args.clipboard {
+        Some(clipboard_name) => {
+            clipboard_configuration(clipboard_name, config)?;
+            vec![clipboard_name]
+            },
+        None => {
+            let mut clipboard_names: Vec<&String>=config.clipboards().keys().collect();
+            clipboard_names.sort();
+            clipboard_names
+            },
+        };
+
+    let mut failed=false;
+    for name in clipboard_names {
+        let configuration=&config.clipboards()[name];
+        println!("{name} ({})", configuration.host());
+
+        for (check, result) in diagnose_clipboard(name, configuration) {
+            let (status, detail)=match result {
+                CheckResult::Pass(detail) => ("PASS", detail),
+                CheckResult::Fail(detail) => ("FAIL", detail),
+                CheckResult::Skip(detail) => ("SKIP", detail),
+                };
+            failed|=status=="FAIL";
+
+            println!("    {status}  {check:<15}{detail}");
+            }
+        }
+
+    if failed {
+        anyhow::bail!("Some of the checks failed");
+        }
 
     Ok(())
     }
@@ -128,10 +405,13 @@ fn daemon_command(_args: &DaemonArgs, config: &Config) -> Result<(), anyhow::Err
 
     for (name, configuration) in config.clipboards() {
 
-        let clipshare=Clipshare::new(configuration.host());
+        let clipshare=Clipshare::with_options(configuration.host(), &configuration.client_options()?)?;
         let shared_clipboard=Rc::new(SharedClipboard::new(name, clipshare, configuration.password()));
 
-        if !configuration.copy_hotkey().is_empty() {
+        // Clipboards with weak passwords can still be pasted from, but not copied to, as warned on startup
+        let copy_allowed=configuration.check_password(name).is_ok();
+
+        if copy_allowed && !configuration.copy_hotkey().is_empty() {
             if let Ok(copy_hotkey)=configuration.copy_hotkey().parse() {
                 match manager.register(copy_hotkey) {
                     Ok(_) => { copy_hotkeys.insert(copy_hotkey.id(), shared_clipboard.clone()); },
@@ -153,7 +433,7 @@ fn daemon_command(_args: &DaemonArgs, config: &Config) -> Result<(), anyhow::Err
                 notify(&format!("Unable to parse paste hotkey of {name} clipboard."), true);
                 }
             }
-        if !configuration.sync_copy_hotkey().is_empty() {
+        if copy_allowed && !configuration.sync_copy_hotkey().is_empty() {
             if let Ok(sync_copy_hotkey)=configuration.sync_copy_hotkey().parse() {
                 match manager.register(sync_copy_hotkey) {
                     Ok(_) => { sync_copy_hotkeys.insert(sync_copy_hotkey.id(), shared_clipboard.clone()); },
@@ -178,6 +458,11 @@ fn daemon_command(_args: &DaemonArgs, config: &Config) -> Result<(), anyhow::Err
 
         }
 
+    let clipboard_configurations: Vec<(String, ClipboardConfig)>=config.clipboards().iter()
+    .map(|(name, configuration)| (name.clone(), configuration.clone()))
+    .collect();
+    std::thread::spawn(move || deliver_queued_copies(clipboard_configurations));
+
     let global_hotkey_channel=GlobalHotKeyEvent::receiver();
 
     event_loop.run(move |_event, event_loop| {
@@ -189,19 +474,19 @@ fn daemon_command(_args: &DaemonArgs, config: &Config) -> Result<(), anyhow::Err
                 }
 
             if copy_hotkeys.contains_key(&event.id()) {
-                copy(copy_hotkeys[&event.id()].clone())
+                copy(copy_hotkeys[&event.id()].clone(), None)
                 .unwrap_or_else(|e| notify_err(e, true));
                 }
             else if paste_hotkeys.contains_key(&event.id()) {
-                paste(paste_hotkeys[&event.id()].clone())
+                paste(paste_hotkeys[&event.id()].clone(), None)
                 .unwrap_or_else(|e| notify_err(e, true));
                 }
             else if sync_copy_hotkeys.contains_key(&event.id()) {
-                sync_copy(sync_copy_hotkeys[&event.id()].clone())
+                sync_copy(sync_copy_hotkeys[&event.id()].clone(), None)
                 .unwrap_or_else(|e| notify_err(e, true));
                 }
             else if sync_paste_hotkeys.contains_key(&event.id()) {
-                sync_paste(sync_paste_hotkeys[&event.id()].clone())
+                sync_paste(sync_paste_hotkeys[&event.id()].clone(), None)
                 .unwrap_or_else(|e| notify_err(e, true));
                 }
             }
@@ -215,13 +500,19 @@ fn copy_command(args: &CopyArgs, config: &Config) -> Result<(), anyhow::Error> {
         None => config.default_clipboard().to_string(),
         };
 
+    clipboard_configuration(&clipboard_name, config)?.check_password(&clipboard_name)?;
+
     let shared_clipboard=get_shared_clipboard(&clipboard_name, config)?;
 
-    if !args.sync_mode {
-        copy(shared_clipboard)?;
+    let progress=terminal_progress("Uploading");
+    if args.stdin {
+        pipe_copy(shared_clipboard, progress)?;
+        }
+    else if !args.sync_mode {
+        copy(shared_clipboard, progress)?;
         }
     else {
-        sync_copy(shared_clipboard)?;
+        sync_copy(shared_clipboard, progress)?;
         }
 
     Ok(())
@@ -234,18 +525,22 @@ fn paste_command(args: &PasteArgs, config: &Config) -> Result<(), anyhow::Error>
 
     let shared_clipboard=get_shared_clipboard(&clipboard_name, config)?;
 
-    if !args.sync_mode {
-        paste(shared_clipboard)?;
+    let progress=terminal_progress("Downloading");
+    if args.stdout {
+        pipe_paste(shared_clipboard, progress)?;
+        }
+    else if !args.sync_mode {
+        paste(shared_clipboard, progress)?;
         }
     else {
-        sync_paste(shared_clipboard)?;
+        sync_paste(shared_clipboard, progress)?;
         }
 
     Ok(())
     }
 
 /// Copyes content from environment to the shared clipboard by emulating a Ctrl+C key press.
-fn copy(shared_clipboard: Rc<SharedClipboard>) -> Result<(), anyhow::Error> {
+fn copy(shared_clipboard: Rc<SharedClipboard>, progress: Option<Progress>) -> Result<(), anyhow::Error> {
     let mut clipboard=CLIPBOARD.lock().unwrap();
 
     let original_system_clipboard_text=clipboard_get_text(&mut clipboard).context("Unable to read from the system clipboard")?;
@@ -267,22 +562,21 @@ fn copy(shared_clipboard: Rc<SharedClipboard>) -> Result<(), anyhow::Error> {
         }
 
     let shared_clipboard_content=SharedClipboardContent::Text(system_clipboard_text);
-    shared_clipboard.set_content(shared_clipboard_content)
+    let queued=OUTBOX.send(&shared_clipboard, shared_clipboard_content, progress)
     .context("Unable to access the shared clipboard")?;
 
     clipboard.set_text(original_system_clipboard_text).context("Unable to write to system clipboard")?;
-    notify(&format!("Copied to {}", shared_clipboard.name()), true);
 
-    Ok(())
+    finish_copy(&shared_clipboard, "Copied", queued, true)
     }
 
 /// Pastes content from environment to the shared clipboard by emulating a Ctrl+C key press.
-fn paste(shared_clipboard: Rc<SharedClipboard>) -> Result<(), anyhow::Error> {
+fn paste(shared_clipboard: Rc<SharedClipboard>, progress: Option<Progress>) -> Result<(), anyhow::Error> {
     let mut clipboard=CLIPBOARD.lock().unwrap();
 
     let original_system_clipboard_text=clipboard_get_text(&mut clipboard).context("Unable to read from the system clipboard")?;
 
-    let shared_clipboard_content=shared_clipboard.get_content()?;
+    let shared_clipboard_content=shared_clipboard.get_content(progress)?;
 
     match shared_clipboard_content {
         SharedClipboardContent::Text(text) => {
@@ -303,7 +597,7 @@ fn paste(shared_clipboard: Rc<SharedClipboard>) -> Result<(), anyhow::Error> {
 
 /// Copyes content from the system clipboard to the shared clipboard.
 /// Note: sync refers to system and shared clipboard synchronization, not to  be confused with programming paradigm.
-fn sync_copy(shared_clipboard: Rc<SharedClipboard>) -> Result<(), anyhow::Error> {
+fn sync_copy(shared_clipboard: Rc<SharedClipboard>, progress: Option<Progress>) -> Result<(), anyhow::Error> {
     let mut clipboard=CLIPBOARD.lock().unwrap();
 
     let content=clipboard_get_text(&mut clipboard).context("Unable to read from the system clipboard")?;
@@ -314,20 +608,18 @@ fn sync_copy(shared_clipboard: Rc<SharedClipboard>) -> Result<(), anyhow::Error>
         }
 
     let shared_clipboard_content=SharedClipboardContent::Text(content);
-    shared_clipboard.set_content(shared_clipboard_content)
+    let queued=OUTBOX.send(&shared_clipboard, shared_clipboard_content, progress)
     .context("Unable to access the shared clipboard")?;
 
-    notify(&format!("Sync-copied to {}", shared_clipboard.name()), true);
-
-    Ok(())
+    finish_copy(&shared_clipboard, "Sync-copied", queued, true)
     }
 
 /// Pastes content from the shared clipboard to the system clipboard.
 /// Note: sync refers to system and shared clipboard synchronization, not to  be confused with programming paradigm.
-fn sync_paste(shared_clipboard: Rc<SharedClipboard>) -> Result<(), anyhow::Error> {
+fn sync_paste(shared_clipboard: Rc<SharedClipboard>, progress: Option<Progress>) -> Result<(), anyhow::Error> {
     let mut clipboard=CLIPBOARD.lock().unwrap();
 
-    let shared_clipboard_content=shared_clipboard.get_content().context("Unable to access the shared clipboard")?;
+    let shared_clipboard_content=shared_clipboard.get_content(progress).context("Unable to access the shared clipboard")?;
 
     match shared_clipboard_content {
         SharedClipboardContent::Text(text) => {
@@ -339,6 +631,67 @@ fn sync_paste(shared_clipboard: Rc<SharedClipboard>) -> Result<(), anyhow::Error
     Ok(())
     }
 
+/// Delivers the copies queued while the servers were unreachable, checking for them periodically.
+/// Runs on its own thread, so the daemon keeps responding to hotkeys while the servers are being reached.
+fn deliver_queued_copies(clipboard_configurations: Vec<(String, ClipboardConfig)>) {
+    let mut shared_clipboards=Vec::new();
+    for (name, configuration) in clipboard_configurations {
+        let clipshare=configuration.client_options()
+        .and_then(|options| Clipshare::with_options(configuration.host(), &options));
+
+        match clipshare {
+            Ok(clipshare) => shared_clipboards.push(SharedClipboard::new(&name, clipshare, configuration.password())),
+            Err(e) => notify_err(e.context(format!("Unable to deliver queued copies to {name}")), true),
+            }
+        }
+
+    loop {
+        for shared_clipboard in &shared_clipboards {
+            match OUTBOX.deliver(shared_clipboard) {
+                Ok(true) => notify(&format!("Delivered the queued copy to {}", shared_clipboard.name()), true),
+                Ok(false) => {},
+                // Still unreachable, the next round will try again
+                Err(e) if is_transient(&e) => {},
+                Err(e) => notify_err(e.context(format!("Unable to deliver the queued copy to {}", shared_clipboard.name())), true),
+                }
+            }
+
+        std::thread::sleep(OUTBOX_DELIVERY_INTERVAL);
+        }
+    }
+
+/// Copies the standard input to the shared clipboard.
+fn pipe_copy(shared_clipboard: Rc<SharedClipboard>, progress: Option<Progress>) -> Result<(), anyhow::Error> {
+    let mut content=String::new();
+    std::io::stdin().read_to_string(&mut content).context("Unable to read text from the standard input")?;
+
+    if content.is_empty() {
+        notify("Nothing to copy", false);
+        return Ok(());
+        }
+
+    let shared_clipboard_content=SharedClipboardContent::Text(content);
+    let queued=OUTBOX.send(&shared_clipboard, shared_clipboard_content, progress)
+    .context("Unable to access the shared clipboard")?;
+
+    finish_copy(&shared_clipboard, "Copied", queued, false)
+    }
+
+/// Writes the content of the shared clipboard to the standard output, exactly as it was copied.
+fn pipe_paste(shared_clipboard: Rc<SharedClipboard>, progress: Option<Progress>) -> Result<(), anyhow::Error> {
+    let shared_clipboard_content=shared_clipboard.get_content(progress).context("Unable to access the shared clipboard")?;
+
+    match shared_clipboard_content {
+        SharedClipboardContent::Text(text) => {
+            let mut stdout=std::io::stdout().lock();
+            stdout.write_all(text.as_bytes()).context("Unable to write to the standard output")?;
+            stdout.flush().context("Unable to write to the standard output")?;
+            },
+        };
+
+    Ok(())
+    }
+
 /// A helper method returning empty string when the system clipboard is empty, instead of throwing an error
 fn clipboard_get_text(clipboard: &mut Clipboard) -> Result<String, arboard::Error> {
     match clipboard.get_text() {
@@ -353,40 +706,250 @@ fn clipboard_get_text(clipboard: &mut Clipboard) -> Result<String, arboard::Erro
         }
     }
 
-/// a wrapper for getting SharedClipboard instance
-fn get_shared_clipboard(clipboard_name: &str, config: &Config) -> Result<Rc<SharedClipboard>, anyhow::Error> {
-    if !config.clipboards().contains_key(clipboard_name) {
-        anyhow::bail!("Unable to find clipboard {clipboard_name}");
+/// Returns a callback printing the progress of large transfers to the terminal, None if stderr is not a terminal.
+fn terminal_progress(action: &'static str) -> Option<Progress> {
+    if !std::io::stderr().is_terminal() {
+        return None;
+        }
+
+    let mut last_percentage=None;
+
+    Some(Box::new(move |transferred, total| {
+        let Some(total)=total.filter(|total| *total>=PROGRESS_THRESHOLD) else {
+            return;
+            };
+
+        let percentage=transferred*100/total;
+        if last_percentage!=Some(percentage) {
+            last_percentage=Some(percentage);
+            eprint!("\r{action} {percentage}%");
+
+            if transferred>=total {
+                eprintln!();
+                }
+            }
+        }))
+    }
+
+/// The outcome of a check of the status command, with its details.
+enum CheckResult {
+    Pass(String),
+    Fail(String),
+    /// The check could not be done, as an earlier one failed or there's nothing to check.
+    Skip(String),
+    }
+
+/// Checks the parts of the way to a shared clipboard one after another, from the configuration to the decryption of its content.
+/// Once a check fails, the ones depending on it are left out.
+fn diagnose_clipboard(name: &str, configuration: &ClipboardConfig) -> Vec<(&'static str, CheckResult)> {
+    let mut checks=Vec::new();
+
+    // Weak passwords don't prevent the access, the other checks go on
+    let password=match (configuration.password_weakness(), configuration.check_password(name)) {
+        (None, _) => CheckResult::Pass(format!("strong, about {:.0} bits of entropy", password::estimate_entropy(configuration.password()))),
+        (Some(weakness), Ok(())) => CheckResult::Pass(format!("weak as {weakness}, allowed by allow_weak_password")),
+        (Some(weakness), Err(_)) => CheckResult::Fail(format!("weak as {weakness}, copying is refused")),
+        };
+    checks.push(("Password", password));
+
+    let clipshare=match configuration.client_options().and_then(|options| Clipshare::with_options(configuration.host(), &options)) {
+        Ok(clipshare) => clipshare,
+        Err(e) => {
+            checks.push(("Configuration", CheckResult::Fail(format!("{e:#}"))));
+            return checks;
+            },
+        };
+    checks.push(("Configuration", CheckResult::Pass("valid".to_string())));
+
+    if !configuration.proxy().is_empty() {
+        checks.push(("DNS", CheckResult::Skip("the host is resolved by the proxy".to_string())));
         }
+    else {
+        match clipshare.resolve_host() {
+            Ok(addresses) => {
+                let mut ips: Vec<String>=addresses.iter().map(|address| address.ip().to_string()).collect();
+                ips.dedup();
+                checks.push(("DNS", CheckResult::Pass(format!("resolved to {}", ips.join(", ")))));
+                },
+            Err(e) => {
+                checks.push(("DNS", CheckResult::Fail(format!("{e:#}"))));
+                return checks;
+                },
+            }
+        }
+
+    // The TLS handshake and the certificate are checked only once the connection itself was made
+    // Behind a proxy, the connection goes to the proxy, which reports failures to reach the server in its own way
+    if let Err(e)=clipshare.check_connection() {
+        let proxied=!configuration.proxy().is_empty();
+        let (connection, tls)=match connection_failure(&e) {
+            Some(ConnectionFailure::Tls) => (
+                CheckResult::Pass("connected".to_string()),
+                CheckResult::Fail(format!("the TLS handshake failed, {e:#}")),
+                ),
+            Some(ConnectionFailure::Certificate) => (
+                CheckResult::Pass("connected".to_string()),
+                CheckResult::Fail(format!("the certificate of the server is not valid, {e:#}")),
+                ),
+            Some(ConnectionFailure::Other) if proxied => (
+                CheckResult::Fail(format!("the proxy refused to connect to the server, {e:#}")),
+                CheckResult::Skip("no connection to the server".to_string()),
+                ),
+            Some(ConnectionFailure::Connect) if proxied => (
+                CheckResult::Fail(format!("unable to connect to the proxy, {e:#}")),
+                CheckResult::Skip("no connection to the server".to_string()),
+                ),
+            Some(ConnectionFailure::Timeout) => (
+                CheckResult::Fail(format!("the server didn't respond in time, {e:#}")),
+                CheckResult::Skip("no connection to the server".to_string()),
+                ),
+            Some(ConnectionFailure::Connect | ConnectionFailure::Other) | None => (
+                CheckResult::Fail(format!("{e:#}")),
+                CheckResult::Skip("no connection to the server".to_string()),
+                ),
+            };
+        checks.push(("Connection", connection));
+        checks.push(("TLS", tls));
+        return checks;
+        }
+    checks.push(("Connection", CheckResult::Pass("connected".to_string())));
+    checks.push(("TLS", CheckResult::Pass("the certificate is valid".to_string())));
+
+    let server=match clipshare.server_info() {
+        Some(server_info) => format!(
+            "version {}, API {}, clipboards of up to {} bytes",
+            server_info.version(), server_info.api_version(), server_info.max_clipboard_size(),
+            ),
+        None => "predates the versioned API, the limits are unknown".to_string(),
+        };
+    checks.push(("Server", CheckResult::Pass(server)));
+
+    // Only the beginning of the content is downloaded, large clipboards would take long to check whole
+    let shared_clipboard=SharedClipboard::new(name, clipshare, configuration.password());
+    let (authorization, decryption)=match shared_clipboard.check_content() {
+        Ok(_) => (
+            CheckResult::Pass("the clipboard ID is authorized".to_string()),
+            CheckResult::Pass("the content decrypts with the configured password".to_string()),
+            ),
+        Err(e) => match e.downcast_ref::<ClipshareError>() {
+            Some(ClipshareError::NotFound) => (
+                CheckResult::Pass("the clipboard ID is authorized".to_string()),
+                CheckResult::Skip("the shared clipboard is empty".to_string()),
+                ),
+            Some(ClipshareError::DecryptionFailed) => (
+                CheckResult::Pass("the clipboard ID is authorized".to_string()),
+                CheckResult::Fail("the content doesn't decrypt with the configured password".to_string()),
+                ),
+            _ => (
+                CheckResult::Fail(format!("{e:#}")),
+                CheckResult::Skip("the content could not be downloaded".to_string()),
+                ),
+            },
+        };
+    checks.push(("Authorization", authorization));
+    checks.push(("Decryption", decryption));
+
+    checks
+    }
+
+/// Prints a clipboard listed by the list command in the plain format.
+fn print_clipboard_info(clipboard_info: &ClipboardInfo) {
+    let default=if clipboard_info.default { " (default)" } else { "" };
+    println!("{}{default}", clipboard_info.name);
+    println!("    Host: {}", clipboard_info.host);
+    println!("    ID: {}", clipboard_info.id);
+
+    if let Some(hotkeys)=&clipboard_info.hotkeys {
+        let hotkeys: Vec<String>=[
+            ("copy", hotkeys.copy),
+            ("paste", hotkeys.paste),
+            ("sync copy", hotkeys.sync_copy),
+            ("sync paste", hotkeys.sync_paste),
+            ]
+        .into_iter()
+        .filter_map(|(action, hotkey)| Some(format!("{action} {}", hotkey?)))
+        .collect();
+
+        println!("    Hotkeys: {}", if hotkeys.is_empty() { "none".to_string() } else { hotkeys.join(", ") });
+        }
+    if let Some(reachable)=clipboard_info.reachable {
+        println!("    Reachable: {}", if reachable { "yes" } else { "no" });
+        }
+    }
+
+/// Looks up the configuration of a clipboard by its name.
+fn clipboard_configuration<'a>(clipboard_name: &str, config: &'a Config) -> Result<&'a ClipboardConfig, anyhow::Error> {
+    config.clipboards().get(clipboard_name)
+    .ok_or_else(|| ConfigError::UnknownClipboard(clipboard_name.to_string()).into())
+    }
 
-    let clipboard_configuration=&config.clipboards()[clipboard_name];
-    let clipshare=Clipshare::new(clipboard_configuration.host());
+/// a wrapper for getting SharedClipboard instance
+fn get_shared_clipboard(clipboard_name: &str, config: &Config) -> Result<Rc<SharedClipboard>, anyhow::Error> {
+    let clipboard_configuration=clipboard_configuration(clipboard_name, config)?;
+    let clipshare=Clipshare::with_options(clipboard_configuration.host(), &clipboard_configuration.client_options()?)?;
     let shared_clipboard=Rc::new(SharedClipboard::new(clipboard_name, clipshare, clipboard_configuration.password()));
 
     Ok(shared_clipboard)
     }
 
+/// Notifies about a finished copy, or fails with CopyQueued if it was queued for later delivery instead of being uploaded.
+/// Commands copying to a queue must not look successful to scripts, which would take the content for shared.
+fn finish_copy(shared_clipboard: &SharedClipboard, action: &str, queued: bool, system_notification: bool) -> Result<(), anyhow::Error> {
+    if queued {
+        return Err(CopyQueued(shared_clipboard.name().to_string()).into());
+        }
+
+    notify(&format!("{action} to {}", shared_clipboard.name()), system_notification);
+
+    Ok(())
+    }
+
 /// Throws a system notification or prints to the console
+/// The console is used also in the console output mode and when the notification can't be shown.
 fn notify(text: &str, system_notification: bool) {
-    if system_notification {
-        Notification::new()
-        .body(text)
-        .show().unwrap();
-        }
-    else {
+    if !system_notification || !show_notification(text) {
         println!("{text}");
         }
     }
 
 /// Throws a system notification or eprints to the console
+/// Errors reported by the server are described precisely, rather than by the context they occurred in.
 fn notify_err(error: anyhow::Error, system_notification: bool) {
-    if system_notification {
-        Notification::new()
-        .body(&format!("{error}"))
-        .show().unwrap();
+    let text=match error.downcast_ref::<ClipshareError>() {
+        Some(clipshare_error) if error.to_string()!=clipshare_error.to_string() => format!("{error}: {clipshare_error}"),
+        _ => format!("{error}"),
+        };
+
+    if !system_notification || !show_notification(&text) {
+        eprintln!("{text}");
         }
-    else {
-        eprintln!("{error}");
+    }
+
+/// Shows a system notification unless in the console output mode, returning whether it was shown.
+fn show_notification(text: &str) -> bool {
+    if CONSOLE_OUTPUT.load(Ordering::Relaxed) {
+        return false;
+        }
+
+    Notification::new()
+    .body(text)
+    .show()
+    .is_ok()
+    }
+
+/// Tells whether system notifications can be shown.
+/// They're delivered through the D-Bus session bus, which is missing on headless machines, in CI or over SSH.
+#[cfg(all(unix, not(target_os="macos")))]
+fn system_notifications_available() -> bool {
+    if std::env::var_os("DBUS_SESSION_BUS_ADDRESS").is_some() {
+        return true;
         }
+
+    // Without the variable, the bus is looked for at its standard location
+    std::env::var_os("XDG_RUNTIME_DIR").is_some_and(|directory| std::path::Path::new(&directory).join("bus").exists())
+    }
+#[cfg(not(all(unix, not(target_os="macos"))))]
+fn system_notifications_available() -> bool {
+    true
     }
 
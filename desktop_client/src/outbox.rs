@@ -0,0 +1,200 @@
+/*
+* Copyright (C) 2023 Rastislav Kish
+*
+* This program is free software: you can redistribute it and/or modify
+* it under the terms of the GNU General Public License as published by
+* the Free Software Foundation, version 3.
+*
+* This program is distributed in the hope that it will be useful,
+* but WITHOUT ANY WARRANTY; without even the implied warranty of
+* MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+* GNU General Public License for more details.
+*
+* You should have received a copy of the GNU General Public License
+* along with this program. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use anyhow::Context;
+use clipshare_client::{is_transient, Progress, SharedClipboardContent};
+use clipshare_client::blocking::SharedClipboard;
+
+/// The outcome of a copy queued in the outbox instead of being uploaded, reported as an error since the content didn't reach the shared clipboard yet.
+#[derive(Debug)]
+pub struct CopyQueued(pub String);
+impl fmt::Display for CopyQueued {
+
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "The server of {} is unreachable, the copy is queued until the daemon delivers it", self.0)
+        }
+    }
+impl std::error::Error for CopyQueued {}
+
+/// Copies which could not be uploaded because the server was unreachable, kept encrypted on disk until they're delivered.
+/// Only the latest copy of every clipboard is kept, as it would overwrite the earlier ones anyway.
+/// Sending and delivering copies of a clipboard is serialized by a lock file, so a queued copy delivered by the daemon can't overwrite a newer one sent by a copy command.
+pub struct Outbox {
+    directory: Option<PathBuf>,
+    }
+impl Outbox {
+
+    pub fn new() -> Outbox {
+        let directory=dirs::data_dir().map(|mut directory| {
+            directory.extend(&["clipshare", "outbox"]);
+            directory
+            });
+
+        Outbox { directory }
+        }
+
+    /// Copies content to the shared clipboard, queueing it if the server can't be reached.
+    /// Returns whether the content was queued rather than uploaded.
+    pub fn send(&self, shared_clipboard: &SharedClipboard, content: SharedClipboardContent, progress: Option<Progress>) -> Result<bool, anyhow::Error> {
+        let _lock=self.lock(shared_clipboard)?;
+
+        match shared_clipboard.set_content(content.clone(), progress) {
+            Ok(()) => {
+                // An older queued copy would overwrite this one once delivered
+                self.remove(shared_clipboard)?;
+                Ok(false)
+                },
+            Err(e) if is_transient(&e) => {
+                self.put(shared_clipboard, &content).context("The server is unreachable and the copy could not be queued")?;
+                Ok(true)
+                },
+            Err(e) => Err(e),
+            }
+        }
+
+    /// Uploads the queued copy of the shared clipboard, returning whether there was one.
+    /// The copy stays queued if the server is still unreachable, otherwise it's removed from the queue even if the upload fails.
+    pub fn deliver(&self, shared_clipboard: &SharedClipboard) -> Result<bool, anyhow::Error> {
+        let _lock=self.lock(shared_clipboard)?;
+
+        let path=self.path(shared_clipboard)?;
+        let sealed_content=match fs::read(&path) {
+            Ok(sealed_content) => sealed_content,
+            Err(e) if e.kind()==io::ErrorKind::NotFound => return Ok(false),
+            Err(e) => return Err(e).with_context(|| format!("Unable to read the queued copy from {path:?}")),
+            };
+
+        let result=shared_clipboard.unseal(&sealed_content)
+        .and_then(|content| shared_clipboard.set_content(content, None));
+
+        match result {
+            Err(e) if is_transient(&e) => Err(e),
+            result => {
+                self.remove(shared_clipboard)?;
+                result.map(|_| true)
+                },
+            }
+        }
+
+    /// Locks the queue of a shared clipboard against other threads and processes, waiting for the current holder.
+    /// The lock is released once the returned file is closed.
+    fn lock(&self, shared_clipboard: &SharedClipboard) -> Result<fs::File, anyhow::Error> {
+        let path=self.path(shared_clipboard)?.with_extension("lock");
+
+        let directory=path.parent().unwrap();
+        fs::create_dir_all(directory).with_context(|| format!("Unable to create the outbox directory {directory:?}"))?;
+
+        let file=fs::OpenOptions::new().create(true).truncate(false).write(true).open(&path)
+        .with_context(|| format!("Unable to open the outbox lock {path:?}"))?;
+        file.lock().with_context(|| format!("Unable to lock the outbox lock {path:?}"))?;
+
+        Ok(file)
+        }
+
+    fn put(&self, shared_clipboard: &SharedClipboard, content: &SharedClipboardContent) -> Result<(), anyhow::Error> {
+        let path=self.path(shared_clipboard)?;
+        let sealed_content=shared_clipboard.seal(content)?;
+
+        // Written aside and renamed, so an interrupted write can't leave a corrupted copy in the queue
+        let temporary_path=path.with_extension("tmp");
+        fs::write(&temporary_path, sealed_content).with_context(|| format!("Unable to write the queued copy to {temporary_path:?}"))?;
+        fs::rename(&temporary_path, &path).with_context(|| format!("Unable to write the queued copy to {path:?}"))?;
+
+        Ok(())
+        }
+
+    fn remove(&self, shared_clipboard: &SharedClipboard) -> Result<(), anyhow::Error> {
+        let path=self.path(shared_clipboard)?;
+
+        match fs::remove_file(&path) {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind()==io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e).with_context(|| format!("Unable to remove the queued copy {path:?}")),
+            }
+        }
+
+    /// The file of the queued copy of a shared clipboard, named by its ID so the names of clipboards don't leak.
+    fn path(&self, shared_clipboard: &SharedClipboard) -> Result<PathBuf, anyhow::Error> {
+        let directory=self.directory.as_ref().context("Unable to find the data directory for the outbox")?;
+
+        Ok(directory.join(shared_clipboard.clipboard_id()))
+        }
+    }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clipshare_client::{ClientOptions, RetryPolicy};
+    use clipshare_client::blocking::Clipshare;
+
+    /// An outbox in a temporary directory, with a clipboard on a server refusing connections.
+    fn unreachable_clipboard() -> (tempfile::TempDir, Outbox, SharedClipboard) {
+        let directory=tempfile::tempdir().unwrap();
+        let outbox=Outbox { directory: Some(directory.path().to_path_buf()) };
+
+        let options=ClientOptions { retry: RetryPolicy { attempts: 1, ..RetryPolicy::default() }, ..ClientOptions::default() };
+        let clipshare=Clipshare::with_options("https://127.0.0.1:1", &options).unwrap();
+        let shared_clipboard=SharedClipboard::new("Test", clipshare, "Some long enough test password");
+
+        (directory, outbox, shared_clipboard)
+        }
+
+    #[test]
+    fn queues_copies_to_unreachable_servers() {
+        let (_directory, outbox, shared_clipboard)=unreachable_clipboard();
+        let content=SharedClipboardContent::Text("content".to_string());
+
+        assert!(outbox.send(&shared_clipboard, content.clone(), None).unwrap());
+
+        let sealed_content=fs::read(outbox.path(&shared_clipboard).unwrap()).unwrap();
+        let SharedClipboardContent::Text(text)=shared_clipboard.unseal(&sealed_content).unwrap();
+        assert_eq!(text, "content");
+
+        // The copy stays queued while the server is unreachable
+        assert!(outbox.deliver(&shared_clipboard).is_err());
+        assert!(outbox.path(&shared_clipboard).unwrap().exists());
+        }
+
+    #[test]
+    fn drops_copies_failing_for_good() {
+        let (_directory, outbox, shared_clipboard)=unreachable_clipboard();
+        assert!(!outbox.deliver(&shared_clipboard).unwrap());
+
+        fs::write(outbox.path(&shared_clipboard).unwrap(), b"corrupted").unwrap();
+        assert!(outbox.deliver(&shared_clipboard).is_err());
+        assert!(!outbox.path(&shared_clipboard).unwrap().exists());
+        assert!(!outbox.deliver(&shared_clipboard).unwrap());
+        }
+
+    #[test]
+    fn locks_the_queue_of_a_clipboard() {
+        let (_directory, outbox, shared_clipboard)=unreachable_clipboard();
+        let lock=outbox.lock(&shared_clipboard).unwrap();
+
+        let path=outbox.path(&shared_clipboard).unwrap().with_extension("lock");
+        let file=fs::File::open(&path).unwrap();
+        assert!(file.try_lock().is_err());
+
+        drop(lock);
+        assert!(file.try_lock().is_ok());
+        }
+    }
+
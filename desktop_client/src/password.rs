@@ -0,0 +1,135 @@
+/*
+* Copyright (C) 2023 Rastislav Kish
+*
+* This program is free software: you can redistribute it and/or modify
+* it under the terms of the GNU General Public License as published by
+* the Free Software Foundation, version 3.
+*
+* This program is distributed in the hope that it will be useful,
+* but WITHOUT ANY WARRANTY; without even the implied warranty of
+* MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+* GNU General Public License for more details.
+*
+* You should have received a copy of the GNU General Public License
+* along with this program. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+/// Passwords estimated to have less entropy are considered weak.
+/// The password is both the encryption key and the source of the clipboard ID, anyone guessing it can read and overwrite the clipboard.
+const MIN_ENTROPY_BITS: f64=72.0;
+
+/// The password of clipboards without configuration, shared by every client started without one.
+pub const DEFAULT_PASSWORD: &str="DefaultPassword";
+/// Passwords used by many people, compared by their letters only, so variations like Password123 are recognized as well.
+const COMMON_PASSWORDS: &[&str]=&[
+    "defaultpassword", "password", "clipshare", "clipboard", "secret", "qwerty", "letmein", "changeme", "admin",
+    ];
+
+/// Tells why a password is weak, None if it's strong enough.
+pub fn weakness(password: &str) -> Option<String> {
+    if password.is_empty() {
+        return Some("it's empty".to_string());
+        }
+    if password==DEFAULT_PASSWORD {
+        return Some("it's the default password, shared by every unconfigured client".to_string());
+        }
+
+    let letters: String=password.chars()
+    .filter(|c| c.is_alphabetic())
+    .flat_map(char::to_lowercase)
+    .collect();
+    if COMMON_PASSWORDS.contains(&letters.as_str()) {
+        return Some("it's a commonly used password".to_string());
+        }
+
+    let entropy=estimate_entropy(password);
+    if entropy<MIN_ENTROPY_BITS {
+        return Some(format!("it's easy to guess, having about {entropy:.0} bits of entropy while at least {MIN_ENTROPY_BITS:.0} are needed"));
+        }
+
+    None
+    }
+
+/// Estimates the entropy of a password in bits, from the sizes of the character classes it uses.
+/// Letters of any alphabet count as lowercase or uppercase ones, so accents don't pass for special characters.
+/// Characters repeating the previous one or continuing its sequence, like in aaa or 123, add only a bit.
+pub fn estimate_entropy(password: &str) -> f64 {
+    let mut pool_size=0;
+    if password.chars().any(|c| c.is_alphabetic() && !c.is_uppercase()) {
+        pool_size+=26;
+        }
+    if password.chars().any(char::is_uppercase) {
+        pool_size+=26;
+        }
+    if password.chars().any(char::is_numeric) {
+        pool_size+=10;
+        }
+    if password.chars().any(|c| !c.is_alphanumeric()) {
+        pool_size+=33;
+        }
+
+    let bits_per_character=f64::from(pool_size).log2();
+
+    let mut entropy=0.0;
+    let mut previous: Option<char>=None;
+    for c in password.chars() {
+        let predictable=previous.is_some_and(|previous| {
+            let distance=c as i64-previous as i64;
+            distance.abs()<=1
+            });
+
+        entropy+=if predictable { 1.0 } else { bits_per_character };
+        previous=Some(c);
+        }
+
+    entropy
+    }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_the_default_password() {
+        assert!(weakness(DEFAULT_PASSWORD).unwrap().contains("default password"));
+        assert!(weakness("").is_some());
+        }
+
+    #[test]
+    fn rejects_variants_of_common_passwords() {
+        for password in ["password", "Password123!", "P4ssw0rd", "qwerty2024", "Clipshare!"] {
+            assert!(weakness(password).is_some(), "{password} was accepted");
+            }
+        assert!(weakness("Pass-word-2024!").unwrap().contains("commonly used"));
+        }
+
+    #[test]
+    fn judges_digit_only_passwords_by_their_length() {
+        assert!(weakness("8203957162").is_some());
+        assert!(estimate_entropy("8203957162")<MIN_ENTROPY_BITS);
+        assert!(weakness("82039571628390456172839405617283940561").is_none());
+        }
+
+    #[test]
+    fn counts_repeated_and_sequential_characters_as_a_bit() {
+        assert_eq!(estimate_entropy("aaaa"), estimate_entropy("a")+3.0);
+        assert_eq!(estimate_entropy("abcd"), estimate_entropy("a")+3.0);
+        assert_eq!(estimate_entropy("4321"), estimate_entropy("4")+3.0);
+        assert!(weakness(&"a".repeat(60)).is_some());
+        assert!(weakness("abcdefghijklmnopqrstuvwxyz0123456789").is_some());
+        }
+
+    #[test]
+    fn counts_accented_letters_as_letters() {
+        assert_eq!(estimate_entropy("čaj"), estimate_entropy("caj"));
+        assert_eq!(estimate_entropy("Žena"), estimate_entropy("Zena"));
+        assert!(estimate_entropy("a!")>estimate_entropy("aä"));
+        }
+
+    #[test]
+    fn accepts_generated_passwords() {
+        assert!(weakness("x7k2p-9qmw3-h4tz8-a0rvd-6jny1-c5eub").is_none());
+        assert!(estimate_entropy("x7k2p-9qmw3-h4tz8-a0rvd-6jny1-c5eub")>150.0);
+        }
+    }
+
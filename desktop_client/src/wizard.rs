@@ -0,0 +1,144 @@
+/*
+* Copyright (C) 2023 Rastislav Kish
+*
+* This program is free software: you can redistribute it and/or modify
+* it under the terms of the GNU General Public License as published by
+* the Free Software Foundation, version 3.
+*
+* This program is distributed in the hope that it will be useful,
+* but WITHOUT ANY WARRANTY; without even the implied warranty of
+* MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+* GNU General Public License for more details.
+*
+* You should have received a copy of the GNU General Public License
+* along with this program. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+use anyhow::Context;
+use clipshare_client::blocking::Clipshare;
+use global_hotkey::hotkey::HotKey;
+use rand::seq::SliceRandom;
+
+use crate::configuration::{ClipboardConfig, Config, PUBLIC_HOST};
+use crate::password;
+
+const DEFAULT_CLIPBOARD_NAME: &str="Primary";
+
+/// Characters of generated passwords, lowercase letters and digits being easy to retype on another device.
+const PASSWORD_CHARACTERS: &[u8]=b"abcdefghijklmnopqrstuvwxyz0123456789";
+/// Generated passwords are made of groups of characters separated by dashes, 6 groups of 5 giving over 150 bits of entropy.
+const PASSWORD_GROUP_COUNT: usize=6;
+const PASSWORD_GROUP_SIZE: usize=5;
+
+/// Walks the user through the creation of a configuration with a single clipboard, validating the answers on the way.
+pub fn create_config() -> Result<Config, anyhow::Error> {
+    println!("This will create a Clipshare configuration with one clipboard. Press Enter to accept the value in brackets.");
+
+    let name=prompt("Clipboard name", DEFAULT_CLIPBOARD_NAME, |_| Ok(()))?;
+    let host=prompt("Server", PUBLIC_HOST, validate_host)?;
+
+    match Clipshare::new(&host) {
+        Ok(clipshare) if clipshare.is_reachable() => {},
+        _ => println!("Warning: The server can't be reached right now, check the address or use clipshare status later."),
+        }
+
+    println!("The password encrypts the clipboard and derives its ID, so all devices sharing the clipboard need the same one.");
+    let mut password=prompt("Password, leave empty to generate one", "", |password| {
+        match password::weakness(password) {
+            Some(weakness) if !password.is_empty() => Err(format!("The password is weak as {weakness}, choose a stronger one or leave it empty to generate one.")),
+            _ => Ok(()),
+            }
+        })?;
+    if password.is_empty() {
+        password=generate_password();
+        println!("Generated password: {password}");
+        println!("Use it on your other devices to share this clipboard with them.");
+        }
+
+    println!("Hotkeys are written like Alt+C or Ctrl+Shift+V, none disables a hotkey.");
+    let copy_hotkey=prompt_hotkey("Copy hotkey", "Alt+C")?;
+    let paste_hotkey=prompt_hotkey("Paste hotkey", "Alt+P")?;
+    let sync_copy_hotkey=prompt_hotkey("Sync copy hotkey", "Alt+Shift+C")?;
+    let sync_paste_hotkey=prompt_hotkey("Sync paste hotkey", "Alt+Shift+P")?;
+
+    let clipboard=ClipboardConfig::new(&host, &password, &copy_hotkey, &paste_hotkey, &sync_copy_hotkey, &sync_paste_hotkey);
+
+    let mut clipboards=HashMap::new();
+    clipboards.insert(name.clone(), clipboard);
+
+    Ok(Config::new(&name, clipboards))
+    }
+
+/// Asks for a value on the console until the answer passes validation, returning the default for an empty answer.
+fn prompt(question: &str, default: &str, validate: impl Fn(&str) -> Result<(), String>) -> Result<String, anyhow::Error> {
+    loop {
+        if default.is_empty() {
+            print!("{question}: ");
+            }
+        else {
+            print!("{question} [{default}]: ");
+            }
+        io::stdout().flush().context("Unable to write to the standard output")?;
+
+        let mut answer=String::new();
+        if io::stdin().read_line(&mut answer).context("Unable to read from the standard input")?==0 {
+            anyhow::bail!("The configuration was cancelled");
+            }
+
+        let answer=match answer.trim() {
+            "" => default,
+            answer => answer,
+            };
+
+        match validate(answer) {
+            Ok(()) => return Ok(answer.to_string()),
+            Err(e) => println!("{e}"),
+            }
+        }
+    }
+
+/// Asks for a hotkey, returning an empty string for a disabled one, as in the configuration.
+fn prompt_hotkey(question: &str, default: &str) -> Result<String, anyhow::Error> {
+    let hotkey=prompt(question, default, |hotkey| {
+        if hotkey=="none" {
+            return Ok(());
+            }
+
+        hotkey.parse::<HotKey>()
+        .map(|_| ())
+        .map_err(|e| format!("Invalid hotkey {hotkey}. {e}"))
+        })?;
+
+    if hotkey=="none" {
+        return Ok(String::new());
+        }
+
+    Ok(hotkey)
+    }
+
+fn validate_host(host: &str) -> Result<(), String> {
+    // Clipshare clients talk only to servers protected by TLS
+    if !host.starts_with("https://") || host.len()<="https://".len() {
+        return Err("The server address has to start with https://, like https://clipshare.example.com:3127".to_string());
+        }
+
+    Ok(())
+    }
+
+/// Generates a random password like x7k2p-9qmw3-...
+fn generate_password() -> String {
+    let mut rng=rand::thread_rng();
+
+    (0..PASSWORD_GROUP_COUNT)
+    .map(|_| {
+        (0..PASSWORD_GROUP_SIZE)
+        .map(|_| *PASSWORD_CHARACTERS.choose(&mut rng).unwrap() as char)
+        .collect::<String>()
+        })
+    .collect::<Vec<String>>()
+    .join("-")
+    }
+
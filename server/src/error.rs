@@ -0,0 +1,119 @@
+/*
+* Copyright (C) 2023 Rastislav Kish
+*
+* This program is free software: you can redistribute it and/or modify
+* it under the terms of the GNU General Public License as published by
+* the Free Software Foundation, version 3.
+*
+* This program is distributed in the hope that it will be useful,
+* but WITHOUT ANY WARRANTY; without even the implied warranty of
+* MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+* GNU General Public License for more details.
+*
+* You should have received a copy of the GNU General Public License
+* along with this program. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use std::time::Duration;
+
+use axum::{
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+    };
+use serde::Serialize;
+
+use crate::monitor::ReservationError;
+use crate::retry_after_seconds;
+
+/// A machine-readable code of an API error, letting the clients react to errors without parsing the messages.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize)]
+#[serde(rename_all="snake_case")]
+pub enum ErrorCode {
+    InvalidId,
+    InvalidContent,
+    Unauthorized,
+    TooLarge,
+    StorageFull,
+    QuotaExceeded,
+    RateLimited,
+    NotFound,
+    BackendUnavailable,
+    InternalError,
+    }
+impl ErrorCode {
+
+    pub fn status(&self) -> StatusCode {
+        match self {
+            ErrorCode::InvalidId => StatusCode::BAD_REQUEST,
+            ErrorCode::InvalidContent => StatusCode::BAD_REQUEST,
+            ErrorCode::Unauthorized => StatusCode::UNAUTHORIZED,
+            ErrorCode::TooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+            ErrorCode::StorageFull => StatusCode::TOO_MANY_REQUESTS,
+            ErrorCode::QuotaExceeded => StatusCode::TOO_MANY_REQUESTS,
+            ErrorCode::RateLimited => StatusCode::TOO_MANY_REQUESTS,
+            ErrorCode::NotFound => StatusCode::NOT_FOUND,
+            ErrorCode::BackendUnavailable => StatusCode::SERVICE_UNAVAILABLE,
+            ErrorCode::InternalError => StatusCode::INTERNAL_SERVER_ERROR,
+            }
+        }
+    }
+
+#[derive(Serialize)]
+struct ErrorBody {
+    code: ErrorCode,
+    message: String,
+    }
+
+#[derive(Serialize)]
+struct ErrorResponse {
+    error: ErrorBody,
+    }
+
+/// An error response of the API, serialized as {"error": {"code": ..., "message": ...}}.
+#[derive(Debug)]
+pub struct ApiError {
+    code: ErrorCode,
+    message: String,
+    retry_after: Option<Duration>,
+    }
+impl ApiError {
+
+    pub fn new(code: ErrorCode, message: &str) -> ApiError {
+        ApiError { code, message: message.to_string(), retry_after: None }
+        }
+
+    /// Sets the time after which the client may retry, sent in the Retry-After header.
+    pub fn with_retry_after(mut self, retry_after: Duration) -> ApiError {
+        self.retry_after=Some(retry_after);
+        self
+        }
+    }
+impl IntoResponse for ApiError {
+
+    fn into_response(self) -> Response {
+        let status=self.code.status();
+        let body=Json(ErrorResponse {
+            error: ErrorBody { code: self.code, message: self.message },
+            });
+
+        match self.retry_after {
+            Some(retry_after) => (status, [(header::RETRY_AFTER, retry_after_seconds(retry_after))], body).into_response(),
+            None => (status, body).into_response(),
+            }
+        }
+    }
+impl From<ReservationError> for ApiError {
+
+    fn from(e: ReservationError) -> ApiError {
+        let message=format!("{e}");
+
+        match e {
+            ReservationError::StorageFull => ApiError::new(ErrorCode::StorageFull, &message),
+            ReservationError::SizeQuotaExceeded(_) => ApiError::new(ErrorCode::TooLarge, &message),
+            ReservationError::WriteQuotaExceeded(retry_after) => ApiError::new(ErrorCode::QuotaExceeded, &message).with_retry_after(retry_after),
+            ReservationError::TooManyUploads => ApiError::new(ErrorCode::QuotaExceeded, &message),
+            }
+        }
+    }
+
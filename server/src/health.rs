@@ -0,0 +1,69 @@
+/*
+* Copyright (C) 2023 Rastislav Kish
+*
+* This program is free software: you can redistribute it and/or modify
+* it under the terms of the GNU General Public License as published by
+* the Free Software Foundation, version 3.
+*
+* This program is distributed in the hope that it will be useful,
+* but WITHOUT ANY WARRANTY; without even the implied warranty of
+* MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+* GNU General Public License for more details.
+*
+* You should have received a copy of the GNU General Public License
+* along with this program. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use axum::http::StatusCode;
+
+use crate::monitor::EvictionPolicy;
+use crate::{CLIPBOARD_MONITOR, CLIPBOARD_STORE, EVICTION_POLICY};
+
+/// Set once the TLS certificates are loaded and the server is about to accept connections.
+pub static TLS_LOADED: AtomicBool=AtomicBool::new(false);
+
+/// Liveness probe, answering as long as the process is able to handle requests.
+pub async fn healthz() -> (StatusCode, String) {
+    (StatusCode::OK, String::from("ok"))
+    }
+
+/// Readiness probe, checking that the storage is reachable, TLS is loaded and there is room for new clipboards.
+/// Responds with a line per check, and 503 if any of them fails.
+pub async fn readyz() -> (StatusCode, String) {
+    let mut ready=true;
+    let mut report=Vec::new();
+
+    match CLIPBOARD_STORE.ping().await {
+        Ok(()) => report.push(String::from("storage: ok")),
+        Err(e) => {
+            // The probe may be publicly reachable, so the details of the failure go only to the log
+            tracing::warn!("Readiness check failed to reach the storage. {e}");
+            ready=false;
+            report.push(String::from("storage: unavailable"));
+            },
+        };
+
+    if TLS_LOADED.load(Ordering::Relaxed) {
+        report.push(String::from("tls: ok"));
+        }
+    else {
+        ready=false;
+        report.push(String::from("tls: not loaded"));
+        }
+
+    // With an eviction policy, a full server still accepts new clipboards
+    if *EVICTION_POLICY==EvictionPolicy::Reject && CLIPBOARD_MONITOR.lock().await.full() {
+        ready=false;
+        report.push(String::from("capacity: full"));
+        }
+    else {
+        report.push(String::from("capacity: ok"));
+        }
+
+    let status=if ready { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+
+    (status, report.join("\n"))
+    }
+
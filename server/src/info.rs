@@ -0,0 +1,69 @@
+/*
+* Copyright (C) 2023 Rastislav Kish
+*
+* This program is free software: you can redistribute it and/or modify
+* it under the terms of the GNU General Public License as published by
+* the Free Software Foundation, version 3.
+*
+* This program is distributed in the hope that it will be useful,
+* but WITHOUT ANY WARRANTY; without even the implied warranty of
+* MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+* GNU General Public License for more details.
+*
+* You should have received a copy of the GNU General Public License
+* along with this program. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use axum::Json;
+use serde::Serialize;
+
+use crate::manifest::MAX_PARTS;
+use crate::{CLIPBOARD_CONTENT_EXPIRATION_TIME, CLIPBOARD_CONTENT_MAX_SIZE, ID_QUOTAS, MAX_USED_SPACE};
+
+/// The version of the API served under /api/v1.
+pub const API_VERSION: u32=1;
+
+/// Optional functionality of the server, letting the clients tell what they can rely on.
+/// Binary bodies and streaming concern the clipboard content, multipart the manifests and parts of large clipboards.
+const FEATURES: &[&str]=&["binary_bodies", "streaming", "multipart"];
+
+#[derive(Serialize)]
+struct TtlBounds {
+    min_seconds: u64,
+    max_seconds: u64,
+    }
+
+/// Capabilities and limits of the server, served by GET /api/v1/info.
+#[derive(Serialize)]
+pub struct ServerInfo {
+    version: &'static str,
+    api_version: u32,
+    /// The maximum size of a single-part clipboard or a single part, in bytes.
+    max_content_size: usize,
+    max_parts: usize,
+    /// The maximum total size of a clipboard under the default quota, in bytes.
+    max_clipboard_size: usize,
+    ttl: TtlBounds,
+    features: &'static [&'static str],
+    }
+
+pub async fn info() -> Json<ServerInfo> {
+    let mut max_clipboard_size=(*CLIPBOARD_CONTENT_MAX_SIZE).saturating_mul(MAX_PARTS).min(*MAX_USED_SPACE);
+    if let Some(max_size)=ID_QUOTAS.default_quota().max_size() {
+        max_clipboard_size=max_clipboard_size.min(max_size);
+        }
+
+    // All clipboards currently share the same expiration time
+    let ttl=CLIPBOARD_CONTENT_EXPIRATION_TIME.as_secs();
+
+    Json(ServerInfo {
+        version: env!("CARGO_PKG_VERSION"),
+        api_version: API_VERSION,
+        max_content_size: *CLIPBOARD_CONTENT_MAX_SIZE,
+        max_parts: MAX_PARTS,
+        max_clipboard_size,
+        ttl: TtlBounds { min_seconds: ttl, max_seconds: ttl },
+        features: FEATURES,
+        })
+    }
+
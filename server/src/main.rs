@@ -16,39 +16,62 @@
 
 use std::collections::HashMap;
 use std::env;
+use std::io;
 use std::net::SocketAddr;
 use std::path::PathBuf;
 use std::str::FromStr;
 use std::time::{Instant, Duration};
 use std::sync::LazyLock;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 
 use tokio::sync::Mutex;
 
 use anyhow::bail;
 use axum::{
-    extract::{DefaultBodyLimit, Path},
-    http::StatusCode,
-    routing::{get},
-    Router,
+    body::{Body, Bytes},
+    extract::{rejection::JsonRejection, ConnectInfo, DefaultBodyLimit, FromRequest, Path, Request},
+    http::{header, HeaderMap, HeaderValue, Method, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
     };
 use axum_server::tls_rustls::RustlsConfig;
-use redis::AsyncCommands;
+use base64::{Engine, engine::general_purpose as base64_eng};
+use futures_util::stream::{self, StreamExt, TryStreamExt};
 use regex::Regex;
 
+mod error;
+mod health;
+mod info;
+mod manifest;
+mod metrics;
+mod monitor;
+mod quota;
+mod rate_limit;
+mod storage;
+
+use crate::error::{ApiError, ErrorCode};
+use crate::manifest::{Manifest, MANIFEST_CONTENT_TYPE, MAX_MANIFEST_SIZE, MAX_PARTS};
+use crate::metrics::MeteredStore;
+use crate::monitor::{ClipboardMonitor, EvictionPolicy, Reservation, ReservationError};
+use crate::quota::{Quota, Quotas};
+use crate::rate_limit::{RateLimit, RateLimiter};
+use crate::storage::{BackendUnavailable, ClipboardStore, ContentStream, FilesystemStore, MemoryStore, RedisStore};
+
+const OCTET_STREAM: &str="application/octet-stream";
+
 static CLIPBOARD_ID_REGEX: LazyLock<Regex> = LazyLock::new(|| Regex::new(
     r"^[a-zA-Z0-9_\-]{32,128}$"
     ).unwrap());
-static CLIPBOARD_CONTENT_REGEX: LazyLock<Regex> = LazyLock::new(|| Regex::new(
-    r"^[a-zA-Z0-9+/]+$"
-    ).unwrap());
+
 static SIZE_REGEX: LazyLock<Regex> = LazyLock::new(|| Regex::new(
     r"^(?<value>\d+)(?<unit>B|K|M|G|T)?$"
     ).unwrap());
-/*
 static TIME_REGEX: LazyLock<Regex> = LazyLock::new(|| Regex::new(
-    r"^(?<value>\d+)(?<unit>S|M|H|Y)?$"
+    r"^(?<value>\d+)(?<unit>S|M|H|D|W|Y)?$"
     ).unwrap());
-*/
 static CLIPBOARD_MONITOR: LazyLock<Mutex<ClipboardMonitor>> = LazyLock::new(|| Mutex::new(ClipboardMonitor::new()));
 static REDIS_HOST: LazyLock<redis::ConnectionInfo> = LazyLock::new(|| {
     if let Ok(host)=std::env::var("REDIS_HOST") {
@@ -59,8 +82,85 @@ static REDIS_HOST: LazyLock<redis::ConnectionInfo> = LazyLock::new(|| {
 
     redis::ConnectionInfo::from_str("redis://127.0.0.1/").unwrap()
     });
-static REDIS_CLIENT: LazyLock<redis::Client> = LazyLock::new(|| {
-    redis::Client::open(REDIS_HOST.clone()).unwrap()
+static CLIPBOARD_STORE: LazyLock<Box<dyn ClipboardStore>> = LazyLock::new(|| {
+    // The tests run against an in-memory storage, so they don't depend on a running backend
+    if cfg!(test) {
+        return Box::new(MeteredStore::new(Box::new(MemoryStore::new())));
+        }
+
+    let store: Box<dyn ClipboardStore>=match env::var("STORAGE_BACKEND").map(|v| v.to_lowercase()).as_deref() {
+        Ok("memory") => Box::new(MemoryStore::new()),
+        Ok("filesystem") => Box::new(FilesystemStore::new(STORAGE_DIR.clone()).unwrap()),
+        Ok(v) => {
+            if v!="redis" {
+                eprintln!("Warning: Unknown storage backend {v} in STORAGE_BACKEND environment variable. Using the default setting.");
+                }
+
+            Box::new(RedisStore::new(REDIS_HOST.clone()).unwrap())
+            },
+        Err(_) => Box::new(RedisStore::new(REDIS_HOST.clone()).unwrap()),
+        };
+
+    Box::new(MeteredStore::new(store))
+    });
+static STORAGE_DIR: LazyLock<PathBuf> = LazyLock::new(|| {
+    if let Ok(v)=env::var("STORAGE_DIR") {
+        return PathBuf::from(&v);
+        }
+
+    panic!("Error: STORAGE_DIR environment variable must be set when using the filesystem storage backend.");
+    });
+static STORAGE_SWEEP_INTERVAL: LazyLock<Duration> = LazyLock::new(|| {
+    if let Ok(v)=env::var("STORAGE_SWEEP_INTERVAL") {
+        match parse_duration(&v) {
+            Ok(duration) => return duration,
+            Err(e) => eprintln!("Warning: Invalid duration in STORAGE_SWEEP_INTERVAL. {e} Using the default value."),
+            };
+        }
+
+    parse_duration("1M").unwrap()
+    });
+static RECONCILIATION_INTERVAL: LazyLock<Duration> = LazyLock::new(|| {
+    if let Ok(v)=env::var("RECONCILIATION_INTERVAL") {
+        match parse_duration(&v) {
+            Ok(duration) => return duration,
+            Err(e) => eprintln!("Warning: Invalid duration in RECONCILIATION_INTERVAL. {e} Using the default value."),
+            };
+        }
+
+    parse_duration("10M").unwrap()
+    });
+static GARBAGE_COLLECTION_INTERVAL: LazyLock<Duration> = LazyLock::new(|| {
+    if let Ok(v)=env::var("GARBAGE_COLLECTION_INTERVAL") {
+        match parse_duration(&v) {
+            Ok(duration) => return duration,
+            Err(e) => eprintln!("Warning: Invalid duration in GARBAGE_COLLECTION_INTERVAL. {e} Using the default value."),
+            };
+        }
+
+    parse_duration("30S").unwrap()
+    });
+static IP_RATE_LIMITER: LazyLock<Option<RateLimiter>> = LazyLock::new(|| {
+    let rate_limit=match env::var("IP_RATE_LIMIT") {
+        Ok(v) => RateLimit::parse(&v).unwrap_or_else(|e| {
+            eprintln!("Warning: Invalid content in IP_RATE_LIMIT, using the default value. {e}");
+            RateLimit::parse("60/1M").unwrap()
+            }),
+        Err(_) => RateLimit::parse("60/1M").unwrap(),
+        };
+
+    rate_limit.map(RateLimiter::new)
+    });
+static ID_RATE_LIMITER: LazyLock<Option<RateLimiter>> = LazyLock::new(|| {
+    let rate_limit=match env::var("ID_RATE_LIMIT") {
+        Ok(v) => RateLimit::parse(&v).unwrap_or_else(|e| {
+            eprintln!("Warning: Invalid content in ID_RATE_LIMIT, using the default value. {e}");
+            RateLimit::parse("30/1M").unwrap()
+            }),
+        Err(_) => RateLimit::parse("30/1M").unwrap(),
+        };
+
+    rate_limit.map(RateLimiter::new)
     });
 static CERT_DIR: LazyLock<PathBuf> = LazyLock::new(|| {
     if let Ok(v)=env::var("CERT_DIR") {
@@ -78,6 +178,18 @@ static CERT_DIR: LazyLock<PathBuf> = LazyLock::new(|| {
 
     panic!("Error: CERT_DIR environment variable not set.");
     });
+static METRICS_PORT: LazyLock<Option<u16>> = LazyLock::new(|| {
+    if let Ok(v)=env::var("METRICS_PORT") {
+        match v.parse::<u16>() {
+            Ok(port) => return Some(port),
+            Err(_) => {
+                eprintln!("Warning: Invalid value in METRICS_PORT environment variable. Not serving metrics.");
+                },
+            }
+        }
+
+    None
+    });
 static SERVER_PORT: LazyLock<u16> = LazyLock::new(|| {
     if let Ok(v)=env::var("SERVER_PORT") {
         match v.parse::<u16>() {
@@ -120,6 +232,35 @@ static MAX_USED_SPACE: LazyLock<usize> = LazyLock::new(|| {
 
     parse_size("500M").unwrap()
     });
+static EVICTION_POLICY: LazyLock<EvictionPolicy> = LazyLock::new(|| {
+    if let Ok(v)=env::var("EVICTION_POLICY") {
+        match v.parse::<EvictionPolicy>() {
+            Ok(policy) => return policy,
+            Err(e) => eprintln!("Warning: Invalid content in EVICTION_POLICY, using the default value. {e}"),
+            };
+        }
+
+    EvictionPolicy::Reject
+    });
+static ID_QUOTAS: LazyLock<Quotas> = LazyLock::new(|| {
+    let default_quota=match env::var("ID_QUOTA") {
+        Ok(v) => Quota::parse(&v).unwrap_or_else(|e| {
+            eprintln!("Warning: Invalid content in ID_QUOTA, using the default value. {e}");
+            Quota::default()
+            }),
+        Err(_) => Quota::default(),
+        };
+    let quotas=Quotas::new(default_quota);
+
+    if let Ok(v)=env::var("ID_QUOTA_OVERRIDES") {
+        match Quotas::new(default_quota).with_overrides(&v) {
+            Ok(quotas) => return quotas,
+            Err(e) => eprintln!("Warning: Invalid content in ID_QUOTA_OVERRIDES, ignoring the overrides. {e}"),
+            };
+        }
+
+    quotas
+    });
 static CLIPBOARD_CONTENT_EXPIRATION_TIME: LazyLock<Duration> = LazyLock::new(|| {
     if let Ok(v)=env::var("CLIPBOARD_CONTENT_EXPIRATION_TIME") {
         match parse_duration(&v) {
@@ -141,179 +282,482 @@ static CLIPBOARD_CONTENT_MAX_SIZE: LazyLock<usize> = LazyLock::new(|| {
     parse_size("5M").unwrap()
     });
 
-#[derive(Clone)]
-pub struct Clipboard {
-    created_at: Instant,
-    size: usize,
-    }
-impl Clipboard {
 
-    pub fn new(created_at: Instant, size: usize) -> Clipboard {
-        Clipboard { created_at, size }
+#[tokio::main]
+async fn main() {
+    tracing_subscriber::fmt::init();
+    metrics::init();
+
+    LazyLock::force(&CLIPBOARD_STORE);
+    reconcile_clipboard_monitor().await;
+    tokio::spawn(sweep_storage());
+    tokio::spawn(collect_garbage());
+    tokio::spawn(async {
+        let mut interval=tokio::time::interval(*RECONCILIATION_INTERVAL);
+        // The first tick completes immediately, and the startup reconciliation has just been done
+        interval.tick().await;
+
+        loop {
+            interval.tick().await;
+            reconcile_clipboard_monitor().await;
+            }
+        });
+
+    // Metrics are served only on a separate plain HTTP admin port if configured, so they're never exposed publicly by accident
+    if let Some(metrics_port)=*METRICS_PORT {
+        let admin_app=Router::new()
+        .route("/metrics", get(metrics::metrics))
+        .route("/healthz", get(health::healthz))
+        .route("/readyz", get(health::readyz));
+        let listener=tokio::net::TcpListener::bind(SocketAddr::from(([0, 0, 0, 0], metrics_port))).await.unwrap();
+
+        tokio::spawn(async move {
+            axum::serve(listener, admin_app).await.unwrap();
+            });
         }
 
-    pub fn created_at(&self) -> Instant {
-        self.created_at
+    let mut public_cert=CERT_DIR.clone();
+    public_cert.push("fullchain.pem");
+    if !public_cert.exists() {
+        panic!("Error: Unable to locate the public certificate in {}", public_cert.display());
         }
-    pub fn size(&self) -> usize {
-        self.size
+
+    let mut private_cert=CERT_DIR.clone();
+    private_cert.push("privkey.pem");
+    if !private_cert.exists() {
+        panic!("Error: Unable to locate the private certificate in {}", private_cert.display());
         }
 
-    pub fn valid(&self) -> bool {
-        let current_time=Instant::now();
+    let rustls_config=RustlsConfig::from_pem_file(public_cert, private_cert).await.unwrap();
+    health::TLS_LOADED.store(true, Ordering::Relaxed);
+
+    let addr=SocketAddr::from(([0, 0, 0, 0], *SERVER_PORT));
+    tracing::debug!("Listening on {}", addr);
+    axum_server::bind_rustls(addr, rustls_config)
+    .serve(router().into_make_service_with_connect_info::<SocketAddr>())
+    .await
+    .unwrap();
+    }
+
+/// The routes served on the server port.
+/// The unversioned clipboard routes are kept for clients predating the versioned API.
+fn router() -> Router {
+    let clipboard_routes=Router::new()
+    .route("/clipboard/{id}", get(get_clipboard).post(set_clipboard))
+    .route("/clipboard/{id}/manifest", post(publish_manifest))
+    .route("/clipboard/{id}/parts/{upload_id}/{number}", get(get_clipboard_part).post(set_clipboard_part))
+    .route_layer(middleware::from_fn(rate_limit));
+    let api_routes=Router::new()
+    .route("/info", get(info::info))
+    .merge(clipboard_routes.clone());
+
+    Router::new()
+    .route("/", get(landing_page))
+    .route("/healthz", get(health::healthz))
+    .route("/readyz", get(health::readyz))
+    .nest(&format!("/api/v{}", info::API_VERSION), api_routes)
+    .merge(clipboard_routes)
+    .layer(middleware::from_fn(metrics::track_requests))
+    .layer(DefaultBodyLimit::max(*CLIPBOARD_CONTENT_MAX_SIZE))
+    }
+
+/// Synchronizes the space accounting of the clipboard monitor with the content of the storage.
+/// Done on startup, since the storage may outlive the server process, and periodically afterwards to correct any drift.
+async fn reconcile_clipboard_monitor() {
+    let listing_started_at=Instant::now();
+
+    match CLIPBOARD_STORE.list().await {
+        Ok(stored_clipboards) => {
+            let mut clipboard_monitor=CLIPBOARD_MONITOR.lock().await;
+            clipboard_monitor.rebuild(stored_clipboards, listing_started_at);
+
+            tracing::debug!("Clipboard monitor reconciled, {} clipboards using {} bytes", clipboard_monitor.clipboard_count(), clipboard_monitor.total_used_space());
+            },
+        Err(e) => tracing::warn!("Unable to list the storage content for clipboard monitor reconciliation. {e}"),
+        };
+    }
+
+/// Periodically evicts expired clipboards from the clipboard monitor, so its accounting stays tight and writes near the space limit don't have to wait for a collection.
+/// Idle rate limiter buckets are dropped as well.
+async fn collect_garbage() {
+    let mut interval=tokio::time::interval(*GARBAGE_COLLECTION_INTERVAL);
+
+    loop {
+        interval.tick().await;
 
-        if current_time.duration_since(self.created_at)>=*CLIPBOARD_CONTENT_EXPIRATION_TIME {
-            return false;
+        // The clipboards are deleted while still holding the monitor, so none of them gets deleted after being written anew
+        {
+            let mut clipboard_monitor=CLIPBOARD_MONITOR.lock().await;
+            let collected_clipboards=clipboard_monitor.garbage_collect();
+            delete_clipboards(&collected_clipboards).await;
             }
 
-        true
+        for rate_limiter in [&*IP_RATE_LIMITER, &*ID_RATE_LIMITER].into_iter().flatten() {
+            rate_limiter.purge_idle();
+            }
         }
     }
 
-pub struct ClipboardMonitor {
-    clipboards: HashMap<String, Clipboard>,
-    total_used_space: usize,
+/// Deletes clipboards evicted by the clipboard monitor from the storage.
+/// The expired clipboards are usually gone already, but not every store expires its content on its own.
+async fn delete_clipboards(ids: &[String]) {
+    for id in ids {
+        if let Err(e)=CLIPBOARD_STORE.delete(id).await {
+            tracing::warn!("Unable to delete clipboard {id} from the storage. {e}");
+            }
+        }
     }
-impl ClipboardMonitor {
 
-    pub fn new() -> ClipboardMonitor {
-        let clipboards=HashMap::with_capacity(*MAX_CLIPBOARD_COUNT);
-        let total_used_space=0_usize;
+/// Periodically removes expired content from the storage, for backends which don't expire it on their own.
+async fn sweep_storage() {
+    let mut interval=tokio::time::interval(*STORAGE_SWEEP_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        match CLIPBOARD_STORE.purge_expired().await {
+            Ok(0) => {},
+            Ok(count) => tracing::debug!("Purged {count} expired clipboards from the storage"),
+            Err(e) => tracing::warn!("Unable to purge expired clipboards from the storage. {e}"),
+            };
+        }
+    }
 
-        ClipboardMonitor { clipboards, total_used_space }
+/// A middleware limiting the request rate of clipboard routes per client IP, and the write rate per clipboard ID.
+/// Transfers of parts are counted once for the whole clipboard, by the request of its manifest, as a clipboard may have up to MAX_PARTS of them.
+async fn rate_limit(ConnectInfo(addr): ConnectInfo<SocketAddr>, Path(parameters): Path<HashMap<String, String>>, request: Request, next: Next) -> Response {
+    if parameters.contains_key("upload_id") {
+        return next.run(request).await;
         }
 
-    pub fn reserve_clipboard(&mut self, id: &str, size: usize) -> Result<(), anyhow::Error> {
-        if !self.clipboard_fits(id, size) {
-            self.garbage_collect();
+    let mut result=match &*IP_RATE_LIMITER {
+        Some(rate_limiter) => rate_limiter.check(&addr.ip().to_string()),
+        None => Ok(()),
+        };
 
-            if !self.clipboard_fits(id, size) {
-                bail!("Storage full");
-                }
+    if result.is_ok() && request.method()==Method::POST {
+        if let Some((rate_limiter, id))=ID_RATE_LIMITER.as_ref().zip(parameters.get("id")) {
+            result=rate_limiter.check(id);
             }
+        }
+
+    if let Err(retry_after)=result {
+        return ApiError::new(ErrorCode::RateLimited, "Too many requests")
+        .with_retry_after(retry_after)
+        .into_response();
+        }
+
+    next.run(request).await
+    }
+
+async fn landing_page() -> axum::response::Html<&'static str> {
+    axum::response::Html(include_str!("landing_page.html"))
+    }
 
-        if self.clipboards.contains_key(id) {
-            self.update_clipboard(id, Clipboard::new(Instant::now(), size));
+/// Returns the clipboard content as raw bytes to clients accepting application/octet-stream.
+/// Other clients get the content encoded as base64 text, as they did before binary bodies were supported.
+async fn get_clipboard(Path(id): Path<String>, headers: HeaderMap) -> Result<Response, ApiError> {
+    validate_id(&id)?;
+
+    if accepts_octet_stream(&headers) {
+        return get_clipboard_stream(&id).await;
+        }
+
+    let clipboard_content=match CLIPBOARD_STORE.get(&id).await {
+        Ok(Some(clipboard_content)) if !clipboard_content.is_empty() => clipboard_content,
+        Ok(_) => return Err(ApiError::new(ErrorCode::NotFound, "Clipboard empty")),
+        Err(e) => return Err(storage_error(&format!("Unable to read clipboard {id} from the storage."), e)),
+        };
+
+    Ok(base64_eng::STANDARD_NO_PAD.encode(clipboard_content).into_response())
+    }
+/// Streams the raw clipboard content from the storage, so large clipboards don't have to be loaded into memory at once.
+/// Manifests of multi-part clipboards are recognized and returned as JSON, for the clients to download the parts.
+async fn get_clipboard_stream(id: &str) -> Result<Response, ApiError> {
+    let (size, mut content_stream)=open_content_stream(id).await?;
+
+    if size<=MAX_MANIFEST_SIZE {
+        let mut content=Vec::with_capacity(size);
+        while let Some(chunk)=content_stream.try_next().await.map_err(|e| storage_error(&format!("Unable to read clipboard {id} from the storage."), e.into()))? {
+            content.extend_from_slice(&chunk);
             }
-        else {
-            self.add_clipboard(id, Clipboard::new(Instant::now(), size));
+
+        if let Some(manifest)=manifest::stored_manifest(&content) {
+            return Ok(([(header::CONTENT_TYPE, MANIFEST_CONTENT_TYPE)], manifest.to_vec()).into_response());
             }
 
-        Ok(())
+        return Ok(([(header::CONTENT_TYPE, OCTET_STREAM)], content).into_response());
         }
 
-    fn add_clipboard(&mut self, id: &str, clipboard: Clipboard) {
-        self.total_used_space+=clipboard.size;
-        self.clipboards.insert(id.to_string(), clipboard);
-        }
-    fn update_clipboard(&mut self, id: &str, clipboard: Clipboard) {
-        self.total_used_space+=clipboard.size-self.clipboards[id].size();
-        self.clipboards.insert(id.to_string(), clipboard);
+    Ok(content_stream_response(size, content_stream))
+    }
+/// Streams a part of a multi-part clipboard, always as raw bytes.
+async fn get_clipboard_part(Path((id, upload_id, number)): Path<(String, String, usize)>) -> Result<Response, ApiError> {
+    validate_id(&id)?;
+    validate_part(&upload_id, number)?;
+
+    let (size, content_stream)=open_content_stream(&manifest::part_id(&id, &upload_id, number)).await?;
+
+    Ok(content_stream_response(size, content_stream))
+    }
+
+async fn open_content_stream(id: &str) -> Result<(usize, ContentStream), ApiError> {
+    match CLIPBOARD_STORE.get_stream(id).await {
+        Ok(Some((size, content_stream))) if size>0 => Ok((size, content_stream)),
+        Ok(_) => Err(ApiError::new(ErrorCode::NotFound, "Clipboard empty")),
+        Err(e) => Err(storage_error(&format!("Unable to read clipboard {id} from the storage."), e)),
         }
-    fn clipboard_fits(&self, id: &str, size: usize) -> bool {
-        let (count_difference, space_difference)=if self.clipboards.contains_key(id) {
-            (0, size-self.clipboards[id].size())
+    }
+fn content_stream_response(size: usize, content_stream: ContentStream) -> Response {
+    let headers=[
+        (header::CONTENT_TYPE, OCTET_STREAM.to_string()),
+        (header::CONTENT_LENGTH, size.to_string()),
+        ];
+
+    (headers, Body::from_stream(content_stream)).into_response()
+    }
+/// Sets the clipboard content from raw bytes if the request is application/octet-stream, from base64 text otherwise.
+/// Either way, the content is stored as raw bytes.
+/// Raw content of a known length is streamed to the storage, anything else is buffered.
+async fn set_clipboard(Path(id): Path<String>, request: Request) -> Result<(), ApiError> {
+    validate_id(&id)?;
+
+    let binary=request.headers().get(header::CONTENT_TYPE)==Some(&HeaderValue::from_static(OCTET_STREAM));
+    if binary {
+        if let Some(content_length)=content_length(request.headers()) {
+            return set_clipboard_stream(&id, content_length, request.into_body()).await;
             }
-        else {
-            (1, size)
-            };
-        self.clipboards.len()+count_difference<=*MAX_CLIPBOARD_COUNT && self.total_used_space+space_difference<=*MAX_USED_SPACE
         }
-    fn garbage_collect(&mut self) {
-        self.clipboards=self.clipboards.iter()
-        .filter(|(_, clipboard)| clipboard.valid())
-        .map(|(id, clipboard)| (id.clone(), clipboard.clone()))
-        .collect();
 
-        self.total_used_space=self.clipboards.values()
-        .fold(0, |total_used_space, clipboard| total_used_space+clipboard.size());
+    let body=match Bytes::from_request(request, &()).await {
+        Ok(body) => body,
+        Err(rejection) if rejection.status()==StatusCode::PAYLOAD_TOO_LARGE => {
+            return Err(ApiError::new(ErrorCode::TooLarge, "Clipboard content too large"));
+            },
+        Err(_) => return Err(ApiError::new(ErrorCode::InvalidContent, "Invalid clipboard content")),
+        };
+    if body.len()>*CLIPBOARD_CONTENT_MAX_SIZE {
+        return Err(ApiError::new(ErrorCode::TooLarge, "Clipboard content too large"));
         }
-    }
-impl Default for ClipboardMonitor {
 
-    fn default() -> ClipboardMonitor {
-        ClipboardMonitor::new()
+    let content=if binary {
+        body.to_vec()
         }
+    else {
+        base64_eng::STANDARD_NO_PAD.decode(&body)
+        .map_err(|_| ApiError::new(ErrorCode::InvalidContent, "Invalid clipboard content"))?
+        };
+    if content.is_empty() {
+        return Err(ApiError::new(ErrorCode::InvalidContent, "Invalid clipboard content"));
+        }
+
+    let reservation=reserve(|clipboard_monitor| clipboard_monitor.reserve_clipboard(&id, content.len())).await?;
+
+    let result=CLIPBOARD_STORE.set(&id, &content, *CLIPBOARD_CONTENT_EXPIRATION_TIME).await
+    .map_err(|e| storage_error(&format!("Unable to write clipboard {id} to the storage."), e));
+    settle(reservation, result).await
     }
-#[tokio::main]
-async fn main() {
-    tracing_subscriber::fmt::init();
+/// Streams raw clipboard content of a declared length to the storage.
+/// The space is reserved upfront from the declared length.
+async fn set_clipboard_stream(id: &str, content_length: usize, body: Body) -> Result<(), ApiError> {
+    validate_content_length(content_length)?;
 
-    let app=Router::new()
-    .route("/", get(landing_page))
-    .route("/clipboard/:id", get(get_clipboard).post(set_clipboard))
-    .layer(DefaultBodyLimit::max(*CLIPBOARD_CONTENT_MAX_SIZE));
+    let reservation=reserve(|clipboard_monitor| clipboard_monitor.reserve_clipboard(id, content_length)).await?;
 
-    let mut public_cert=CERT_DIR.clone();
-    public_cert.push("fullchain.pem");
-    if !public_cert.exists() {
-        panic!("Error: Unable to locate the public certificate in {}", public_cert.display());
+    let result=store_content_stream(id, content_length, body).await;
+    settle(reservation, result).await
+    }
+/// Stores a part of a multi-part clipboard, streaming it like set_clipboard_stream.
+/// The parts become visible to the readers only once the manifest listing them gets published.
+async fn set_clipboard_part(Path((id, upload_id, number)): Path<(String, String, usize)>, request: Request) -> Result<(), ApiError> {
+    validate_id(&id)?;
+    validate_part(&upload_id, number)?;
+
+    let Some(content_length)=content_length(request.headers()) else {
+        return Err(ApiError::new(ErrorCode::InvalidContent, "Clipboard parts require a Content-Length"));
+        };
+    validate_content_length(content_length)?;
+
+    let reservation=reserve(|clipboard_monitor| clipboard_monitor.reserve_part(&id, &upload_id, number, content_length)).await?;
+
+    let result=store_content_stream(&manifest::part_id(&id, &upload_id, number), content_length, request.into_body()).await;
+    settle(reservation, result).await
+    }
+/// Publishes a multi-part clipboard, replacing the content of the clipboard with the manifest in a single write.
+/// All parts must have been uploaded, so the readers never see a partial set.
+/// The parts get the time to live of the manifest, so none of them expires while the manifest is still served.
+/// Parts of the replaced content are left to expire, as readers may still be downloading them.
+async fn publish_manifest(Path(id): Path<String>, manifest: Result<Json<Manifest>, JsonRejection>) -> Result<(), ApiError> {
+    validate_id(&id)?;
+
+    let Ok(Json(manifest))=manifest else {
+        return Err(ApiError::new(ErrorCode::InvalidContent, "Invalid manifest"));
+        };
+    if let Err(e)=manifest.validate() {
+        return Err(ApiError::new(ErrorCode::InvalidContent, &format!("{e}")));
         }
 
-    let mut private_cert=CERT_DIR.clone();
-    private_cert.push("privkey.pem");
-    if !private_cert.exists() {
-        panic!("Error: Unable to locate the private certificate in {}", private_cert.display());
+    let part_ids: Vec<String>=(0..manifest.parts().len())
+    .map(|number| manifest::part_id(&id, manifest.upload_id(), number))
+    .collect();
+
+    for (number, (part_id, part)) in part_ids.iter().zip(manifest.parts()).enumerate() {
+        match CLIPBOARD_STORE.size(part_id).await {
+            Ok(Some(size)) if size==part.size() => {},
+            Ok(_) => return Err(ApiError::new(ErrorCode::InvalidContent, &format!("Part {number} is missing or incomplete"))),
+            Err(e) => return Err(storage_error(&format!("Unable to read clipboard part {part_id} from the storage."), e)),
+            };
+        }
+    for (number, part_id) in part_ids.iter().enumerate() {
+        match CLIPBOARD_STORE.expire(part_id, *CLIPBOARD_CONTENT_EXPIRATION_TIME).await {
+            Ok(true) => {},
+            Ok(false) => return Err(ApiError::new(ErrorCode::InvalidContent, &format!("Part {number} is missing or incomplete"))),
+            Err(e) => return Err(storage_error(&format!("Unable to refresh clipboard part {part_id} in the storage."), e)),
+            };
         }
 
-    let rustls_config=RustlsConfig::from_pem_file(public_cert, private_cert).await.unwrap();
+    let stored_manifest=match manifest.to_stored() {
+        Ok(stored_manifest) => stored_manifest,
+        Err(e) => {
+            tracing::error!("Unable to serialize manifest of clipboard {id}. {e}");
+            return Err(ApiError::new(ErrorCode::InternalError, "Internal server error"));
+            },
+        };
 
-    let addr=SocketAddr::from(([0, 0, 0, 0], *SERVER_PORT));
-    tracing::debug!("Listening on {}", addr);
-    axum_server::bind_rustls(addr, rustls_config)
-    .serve(app.into_make_service())
-    .await
-    .unwrap();
+    let reservation=reserve(|clipboard_monitor| {
+        let reservation=clipboard_monitor.reserve_manifest(&id, manifest.upload_id(), stored_manifest.len(), manifest.content_size())?;
+        clipboard_monitor.refresh_clipboards(&part_ids);
+
+        Ok(reservation)
+        }).await?;
+
+    let result=CLIPBOARD_STORE.set(&id, &stored_manifest, *CLIPBOARD_CONTENT_EXPIRATION_TIME).await
+    .map_err(|e| storage_error(&format!("Unable to write clipboard {id} to the storage."), e));
+    settle(reservation, result).await
     }
 
-async fn landing_page() -> axum::response::Html<&'static str> {
-    axum::response::Html(include_str!("landing_page.html"))
+/// Reserves space for a clipboard in the clipboard monitor, deleting the clipboards evicted to make room.
+/// They're deleted while still holding the monitor, so none of them gets deleted after being reserved and written anew.
+async fn reserve(reserve: impl FnOnce(&mut ClipboardMonitor) -> Result<Reservation, ReservationError>) -> Result<Reservation, ApiError> {
+    let mut clipboard_monitor=CLIPBOARD_MONITOR.lock().await;
+    let reservation=reserve(&mut clipboard_monitor)?;
+    delete_clipboards(reservation.evicted_clipboards()).await;
+
+    Ok(reservation)
+    }
+/// Releases the reservation if its clipboard failed to be written, so space isn't held for content which never made it to the storage.
+async fn settle(reservation: Reservation, result: Result<(), ApiError>) -> Result<(), ApiError> {
+    if result.is_err() {
+        CLIPBOARD_MONITOR.lock().await.release(&reservation);
+        }
+
+    result
     }
 
-async fn get_clipboard(Path(id): Path<String>) -> (StatusCode, String) {
-    if !CLIPBOARD_ID_REGEX.is_match(&id) {
-        return (StatusCode::BAD_REQUEST, String::from("Invalid clipboard ID"));
+fn validate_content_length(content_length: usize) -> Result<(), ApiError> {
+    if content_length>*CLIPBOARD_CONTENT_MAX_SIZE {
+        return Err(ApiError::new(ErrorCode::TooLarge, "Clipboard content too large"));
         }
-    if !RESTRICTED_TO.is_empty() && !RESTRICTED_TO.contains(&id) {
-        return (StatusCode::UNAUTHORIZED, String::from("Unauthorized clipboard ID"));
+    if content_length==0 {
+        return Err(ApiError::new(ErrorCode::InvalidContent, "Invalid clipboard content"));
         }
 
-    if let Ok(mut connection)=REDIS_CLIENT.get_async_connection().await {
-        if let Ok(clipboard_content)=connection.get::<String, String>(format!("clipboard::{id}")).await {
-            if !clipboard_content.is_empty() {
-                return (StatusCode::OK, clipboard_content);
-                }
+    Ok(())
+    }
+
+/// Streams content of a declared length to the storage, cutting the body off as soon as it doesn't match the length.
+async fn store_content_stream(id: &str, content_length: usize, body: Body) -> Result<(), ApiError> {
+    // Telling apart a broken upload from a failing storage, as both surface as an error of the store
+    let body_failed=Arc::new(AtomicBool::new(false));
+    let content_stream=stream::try_unfold((body.into_data_stream(), 0), move |(mut data_stream, received)| async move {
+        match data_stream.try_next().await.map_err(io::Error::other)? {
+            Some(chunk) => {
+                let received=received+chunk.len();
+                if received>content_length {
+                    return Err(io::Error::other("Clipboard content longer than declared"));
+                    }
+
+                Ok(Some((chunk, (data_stream, received))))
+                },
+            None if received<content_length => Err(io::Error::other("Clipboard content shorter than declared")),
+            None => Ok(None),
+            }
+        })
+    .inspect_err({
+        let body_failed=body_failed.clone();
+        move |_| body_failed.store(true, Ordering::Relaxed)
+        })
+    .boxed();
+
+    // A failed upload leaves the previous content in place
+    if let Err(e)=CLIPBOARD_STORE.set_stream(id, content_stream, *CLIPBOARD_CONTENT_EXPIRATION_TIME).await {
+        if body_failed.load(Ordering::Relaxed) {
+            tracing::debug!("Upload of clipboard {id} failed. {e}");
+            return Err(ApiError::new(ErrorCode::InvalidContent, "Invalid clipboard content"));
             }
 
-        return (StatusCode::NOT_FOUND, String::from("Clipboard empty"));
+        return Err(storage_error(&format!("Unable to write clipboard {id} to the storage."), e));
         }
 
-    (StatusCode::INTERNAL_SERVER_ERROR, String::from("Internal server error"))
+    Ok(())
+    }
+
+/// The Content-Length of a request, None if not present or malformed.
+fn content_length(headers: &HeaderMap) -> Option<usize> {
+    headers.get(header::CONTENT_LENGTH)?
+    .to_str().ok()?
+    .parse().ok()
     }
-async fn set_clipboard(Path(id): Path<String>, body: String) -> (StatusCode, String) {
-    if !CLIPBOARD_ID_REGEX.is_match(&id) {
-        return (StatusCode::BAD_REQUEST, String::from("Invalid clipboard ID"));
+
+/// Whether the Accept header of a request lists application/octet-stream.
+fn accepts_octet_stream(headers: &HeaderMap) -> bool {
+    headers.get_all(header::ACCEPT).iter()
+    .filter_map(|value| value.to_str().ok())
+    .flat_map(|value| value.split(','))
+    .any(|media_type| media_type.split(';').next().unwrap_or("").trim().eq_ignore_ascii_case(OCTET_STREAM))
+    }
+
+/// Checks the clipboard ID is well formed and allowed on this server.
+fn validate_id(id: &str) -> Result<(), ApiError> {
+    if !CLIPBOARD_ID_REGEX.is_match(id) {
+        return Err(ApiError::new(ErrorCode::InvalidId, "Invalid clipboard ID"));
         }
-    if !RESTRICTED_TO.is_empty() && RESTRICTED_TO.contains(&id) {
-        return (StatusCode::UNAUTHORIZED, String::from("Unauthorised ID"));
+    if !id_allowed(id, &RESTRICTED_TO) {
+        return Err(ApiError::new(ErrorCode::Unauthorized, "Unauthorized clipboard ID"));
         }
-    if body.len()>*CLIPBOARD_CONTENT_MAX_SIZE || !CLIPBOARD_CONTENT_REGEX.is_match(&body) {
-        return (StatusCode::BAD_REQUEST, String::from("Invalid clipboard content"));
+
+    Ok(())
+    }
+/// Whether a clipboard ID may be used on a server restricted to the given IDs, an empty list allows any ID.
+/// The same check applies to reads and writes, writes used to be refused for the listed IDs and allowed for all others.
+fn id_allowed(id: &str, restricted_to: &[String]) -> bool {
+    restricted_to.is_empty() || restricted_to.iter().any(|restricted_id| restricted_id==id)
+    }
+
+fn validate_part(upload_id: &str, number: usize) -> Result<(), ApiError> {
+    if manifest::validate_upload_id(upload_id).is_err() || number>=MAX_PARTS {
+        return Err(ApiError::new(ErrorCode::InvalidId, "Invalid clipboard part"));
         }
 
-    if let Ok(mut connection)=REDIS_CLIENT.get_async_connection().await {
-        let mut clipboard_monitor=CLIPBOARD_MONITOR.lock().await;
-        if let Err(e)=clipboard_monitor.reserve_clipboard(&id, body.len()) {
-            return (StatusCode::TOO_MANY_REQUESTS, format!("{e}"));
-            }
-        drop(clipboard_monitor);
+    Ok(())
+    }
 
-        if connection.set_ex(&format!("clipboard::{id}"), &body, CLIPBOARD_CONTENT_EXPIRATION_TIME.as_secs() as usize).await==Ok(()) {
-            return (StatusCode::OK, String::new());
-            }
+/// Logs a failed storage operation and turns it into an API error.
+/// An unreachable backend is reported separately, so the clients know the problem is temporary.
+fn storage_error(message: &str, e: anyhow::Error) -> ApiError {
+    if e.is::<BackendUnavailable>() {
+        tracing::error!("{message} The storage backend is unreachable. {e}");
+        return ApiError::new(ErrorCode::BackendUnavailable, "Storage backend unavailable");
         }
 
-    (StatusCode::INTERNAL_SERVER_ERROR, String::from("Internal server error"))
+    tracing::error!("{message} {e}");
+    ApiError::new(ErrorCode::InternalError, "Internal server error")
+    }
+
+/// Formats a duration for the Retry-After header, which takes whole seconds.
+/// Rounding up, so the client doesn't come back too early.
+fn retry_after_seconds(duration: Duration) -> String {
+    (duration.as_secs()+u64::from(duration.subsec_nanos()>0)).to_string()
     }
 
 fn parse_size(size: &str) -> Result<usize, anyhow::Error> {
@@ -322,7 +766,7 @@ fn parse_size(size: &str) -> Result<usize, anyhow::Error> {
     if let Some(caps)=SIZE_REGEX.captures(&size) {
         let mut value: usize=caps["value"].parse().unwrap();
 
-        value=match &caps["unit"] {
+        value=match caps.name("unit").map_or("", |unit| unit.as_str()) {
             "B" => value,
             "K" => 1000*value,
             "M" => 1000000*value,
@@ -339,10 +783,10 @@ fn parse_size(size: &str) -> Result<usize, anyhow::Error> {
 fn parse_duration(duration: &str) -> Result<Duration, anyhow::Error> {
     let duration=duration.to_uppercase();
 
-    if let Some(caps)=SIZE_REGEX.captures(&duration) {
+    if let Some(caps)=TIME_REGEX.captures(&duration) {
         let mut value: u64=caps["value"].parse().unwrap();
 
-        value=match &caps["unit"] {
+        value=match caps.name("unit").map_or("", |unit| unit.as_str()) {
             "S" => value,
             "M" => 60*value,
             "H" => 60*60*value,
@@ -357,3 +801,67 @@ fn parse_duration(duration: &str) -> Result<Duration, anyhow::Error> {
 
     bail!("Invalid duration {duration}");
     }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allows_any_id_without_restriction() {
+        assert!(id_allowed("a", &[]));
+        }
+
+    #[test]
+    fn allows_only_listed_ids_with_restriction() {
+        let restricted_to=vec!["a".to_string(), "b".to_string()];
+
+        assert!(id_allowed("a", &restricted_to));
+        assert!(id_allowed("b", &restricted_to));
+        assert!(!id_allowed("c", &restricted_to));
+        }
+
+    /// Sends a request to the router, as if coming from a local client, returning the status and the body of the response.
+    async fn send(request: Request) -> (StatusCode, Bytes) {
+        use axum::extract::connect_info::MockConnectInfo;
+        use tower::ServiceExt;
+
+        let response=router()
+        .layer(MockConnectInfo(SocketAddr::from(([127, 0, 0, 1], 0))))
+        .oneshot(request).await.unwrap();
+        let status=response.status();
+
+        (status, axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap())
+        }
+
+    #[tokio::test]
+    async fn serves_base64_clipboards_on_the_unversioned_route() {
+        let uri=format!("/clipboard/{}", "a".repeat(32));
+
+        let (status, _)=send(Request::get(&uri).body(Body::empty()).unwrap()).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let (status, _)=send(Request::post(&uri).body(Body::from("Y29udGVudA")).unwrap()).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let (status, body)=send(Request::get(&uri).body(Body::empty()).unwrap()).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, "Y29udGVudA");
+        }
+
+    #[tokio::test]
+    async fn serves_binary_clipboards_on_the_versioned_route() {
+        let uri=format!("/api/v{}/clipboard/{}", info::API_VERSION, "b".repeat(32));
+
+        let request=Request::post(&uri)
+        .header(header::CONTENT_TYPE, OCTET_STREAM)
+        .header(header::CONTENT_LENGTH, 7)
+        .body(Body::from("content")).unwrap();
+        let (status, _)=send(request).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let (status, body)=send(Request::get(&uri).header(header::ACCEPT, OCTET_STREAM).body(Body::empty()).unwrap()).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, "content");
+        }
+    }
+
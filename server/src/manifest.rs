@@ -0,0 +1,123 @@
+/*
+* Copyright (C) 2023 Rastislav Kish
+*
+* This program is free software: you can redistribute it and/or modify
+* it under the terms of the GNU General Public License as published by
+* the Free Software Foundation, version 3.
+*
+* This program is distributed in the hope that it will be useful,
+* but WITHOUT ANY WARRANTY; without even the implied warranty of
+* MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+* GNU General Public License for more details.
+*
+* You should have received a copy of the GNU General Public License
+* along with this program. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use std::sync::LazyLock;
+
+use anyhow::bail;
+use regex::Regex;
+use serde::{Serialize, Deserialize};
+
+/// Prefix of stored manifests, telling them apart from the encrypted content of single-part clipboards.
+/// Long enough for encrypted content to never start with it by chance.
+pub const MANIFEST_MAGIC: &[u8]=b"clipshare-manifest\n";
+/// Content type of manifests returned to the clients.
+pub const MANIFEST_CONTENT_TYPE: &str="application/vnd.clipshare.manifest+json";
+/// The maximum number of parts of a clipboard.
+pub const MAX_PARTS: usize=1024;
+/// Stored content larger than this can't be a manifest, so it's streamed without looking for one.
+pub const MAX_MANIFEST_SIZE: usize=MANIFEST_MAGIC.len()+MAX_PARTS*128;
+
+static UPLOAD_ID_REGEX: LazyLock<Regex> = LazyLock::new(|| Regex::new(
+    r"^[a-zA-Z0-9]{16,32}$"
+    ).unwrap());
+static SHA256_REGEX: LazyLock<Regex> = LazyLock::new(|| Regex::new(
+    r"^[0-9a-f]{64}$"
+    ).unwrap());
+
+/// A part of a multi-part clipboard as listed in its manifest.
+/// The hash is checked by the reading clients, the server only makes sure the part exists with the listed size.
+#[derive(Serialize, Deserialize)]
+pub struct ManifestPart {
+    size: usize,
+    sha256: String,
+    }
+impl ManifestPart {
+
+    pub fn size(&self) -> usize {
+        self.size
+        }
+    }
+
+/// The content of a multi-part clipboard, listing its parts in order.
+/// The parts are uploaded under a unique upload ID before the manifest, so publishing the manifest switches the clipboard to the new content at once.
+#[derive(Serialize, Deserialize)]
+pub struct Manifest {
+    upload_id: String,
+    parts: Vec<ManifestPart>,
+    }
+impl Manifest {
+
+    /// Checks the manifest is well formed, not whether its parts exist.
+    pub fn validate(&self) -> Result<(), anyhow::Error> {
+        validate_upload_id(&self.upload_id)?;
+
+        if self.parts.is_empty() || self.parts.len()>MAX_PARTS {
+            bail!("A manifest must list 1 to {MAX_PARTS} parts");
+            }
+        if self.parts.iter().any(|part| part.size==0 || !SHA256_REGEX.is_match(&part.sha256)) {
+            bail!("Invalid part in manifest");
+            }
+
+        Ok(())
+        }
+
+    pub fn upload_id(&self) -> &str {
+        &self.upload_id
+        }
+    pub fn parts(&self) -> &[ManifestPart] {
+        &self.parts
+        }
+
+    /// The total size of all parts.
+    pub fn content_size(&self) -> usize {
+        self.parts.iter().map(|part| part.size).sum()
+        }
+
+    /// Serializes the manifest for the storage, prefixed with MANIFEST_MAGIC.
+    pub fn to_stored(&self) -> Result<Vec<u8>, anyhow::Error> {
+        let mut stored=MANIFEST_MAGIC.to_vec();
+        serde_json::to_writer(&mut stored, self)?;
+
+        Ok(stored)
+        }
+    }
+
+/// Returns the manifest JSON of stored content, None if the content is not a manifest.
+pub fn stored_manifest(content: &[u8]) -> Option<&[u8]> {
+    content.strip_prefix(MANIFEST_MAGIC)
+    }
+
+pub fn validate_upload_id(upload_id: &str) -> Result<(), anyhow::Error> {
+    if !UPLOAD_ID_REGEX.is_match(upload_id) {
+        bail!("Invalid upload ID");
+        }
+
+    Ok(())
+    }
+
+/// The ID under which a part of a clipboard is stored.
+/// The dots keep it apart from clipboard IDs, which can't contain them.
+pub fn part_id(id: &str, upload_id: &str, number: usize) -> String {
+    format!("{id}.{upload_id}.{number}")
+    }
+/// Splits the ID of a part into the clipboard ID and the upload ID, None if the ID is not one of a part.
+pub fn parse_part_id(part_id: &str) -> Option<(&str, &str)> {
+    let mut components=part_id.splitn(3, '.');
+    let (id, upload_id, _)=(components.next()?, components.next()?, components.next()?);
+
+    Some((id, upload_id))
+    }
+
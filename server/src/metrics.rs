@@ -0,0 +1,189 @@
+/*
+* Copyright (C) 2023 Rastislav Kish
+*
+* This program is free software: you can redistribute it and/or modify
+* it under the terms of the GNU General Public License as published by
+* the Free Software Foundation, version 3.
+*
+* This program is distributed in the hope that it will be useful,
+* but WITHOUT ANY WARRANTY; without even the implied warranty of
+* MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+* GNU General Public License for more details.
+*
+* You should have received a copy of the GNU General Public License
+* along with this program. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use std::sync::LazyLock;
+use std::time::{Instant, Duration};
+
+use async_trait::async_trait;
+use axum::{
+    extract::{MatchedPath, Request},
+    http::{header, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    };
+use prometheus::{
+    Encoder, Histogram, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge, Opts, Registry, TextEncoder,
+    };
+
+use crate::error::{ApiError, ErrorCode};
+use crate::storage::{ClipboardStore, ContentStream, StoredClipboard};
+use crate::CLIPBOARD_MONITOR;
+
+static REGISTRY: LazyLock<Registry> = LazyLock::new(Registry::new);
+
+static HTTP_REQUESTS: LazyLock<IntCounterVec> = LazyLock::new(|| register(IntCounterVec::new(
+    Opts::new("clipshare_http_requests_total", "Number of handled HTTP requests"),
+    &["route", "method", "status"],
+    ).unwrap()));
+static HTTP_REQUEST_DURATION: LazyLock<HistogramVec> = LazyLock::new(|| register(HistogramVec::new(
+    HistogramOpts::new("clipshare_http_request_duration_seconds", "Latency of handled HTTP requests"),
+    &["route", "method", "status"],
+    ).unwrap()));
+static USED_SPACE: LazyLock<IntGauge> = LazyLock::new(|| register(IntGauge::new(
+    "clipshare_used_space_bytes", "Space used by all clipboards",
+    ).unwrap()));
+static CLIPBOARD_COUNT: LazyLock<IntGauge> = LazyLock::new(|| register(IntGauge::new(
+    "clipshare_clipboards", "Number of clipboards on the server",
+    ).unwrap()));
+pub static GARBAGE_COLLECTIONS: LazyLock<IntCounter> = LazyLock::new(|| register(IntCounter::new(
+    "clipshare_garbage_collections_total", "Number of clipboard monitor garbage collection runs",
+    ).unwrap()));
+pub static COLLECTED_CLIPBOARDS: LazyLock<IntCounter> = LazyLock::new(|| register(IntCounter::new(
+    "clipshare_collected_clipboards_total", "Number of expired clipboards removed by garbage collection",
+    ).unwrap()));
+pub static EVICTIONS: LazyLock<IntCounter> = LazyLock::new(|| register(IntCounter::new(
+    "clipshare_evictions_total", "Number of valid clipboards evicted to make room for new ones",
+    ).unwrap()));
+static STORAGE_ERRORS: LazyLock<IntCounterVec> = LazyLock::new(|| register(IntCounterVec::new(
+    Opts::new("clipshare_storage_errors_total", "Number of failed storage backend operations"),
+    &["operation"],
+    ).unwrap()));
+static STORAGE_OPERATION_DURATION: LazyLock<Histogram> = LazyLock::new(|| register(Histogram::with_opts(
+    HistogramOpts::new("clipshare_storage_operation_duration_seconds", "Latency of storage backend operations"),
+    ).unwrap()));
+
+/// Registers all metrics, so they're exported from the start rather than after their first use.
+pub fn init() {
+    LazyLock::force(&HTTP_REQUESTS);
+    LazyLock::force(&HTTP_REQUEST_DURATION);
+    LazyLock::force(&USED_SPACE);
+    LazyLock::force(&CLIPBOARD_COUNT);
+    LazyLock::force(&GARBAGE_COLLECTIONS);
+    LazyLock::force(&COLLECTED_CLIPBOARDS);
+    LazyLock::force(&EVICTIONS);
+    LazyLock::force(&STORAGE_ERRORS);
+    LazyLock::force(&STORAGE_OPERATION_DURATION);
+    }
+
+fn register<T: prometheus::core::Collector+Clone+'static>(collector: T) -> T {
+    REGISTRY.register(Box::new(collector.clone())).unwrap();
+
+    collector
+    }
+
+/// A middleware recording the count and latency of requests per route, method and status.
+pub async fn track_requests(request: Request, next: Next) -> Response {
+    // Using the route pattern rather than the actual path, so clipboard IDs don't end up in labels
+    let route=match request.extensions().get::<MatchedPath>() {
+        Some(path) => path.as_str().to_string(),
+        None => String::from("unmatched"),
+        };
+    let method=request.method().to_string();
+
+    let started_at=Instant::now();
+    let response=next.run(request).await;
+    let elapsed=started_at.elapsed();
+
+    let status=response.status().as_u16().to_string();
+    let labels=[route.as_str(), method.as_str(), status.as_str()];
+    HTTP_REQUESTS.with_label_values(&labels).inc();
+    HTTP_REQUEST_DURATION.with_label_values(&labels).observe(elapsed.as_secs_f64());
+
+    response
+    }
+
+/// Serves the metrics in the Prometheus text format.
+pub async fn metrics() -> Response {
+    let clipboard_monitor=CLIPBOARD_MONITOR.lock().await;
+    USED_SPACE.set(clipboard_monitor.total_used_space() as i64);
+    CLIPBOARD_COUNT.set(clipboard_monitor.clipboard_count() as i64);
+    drop(clipboard_monitor);
+
+    let encoder=TextEncoder::new();
+    let mut buffer=Vec::new();
+    if let Err(e)=encoder.encode(&REGISTRY.gather(), &mut buffer) {
+        tracing::error!("Unable to encode metrics. {e}");
+        return ApiError::new(ErrorCode::InternalError, "Internal server error").into_response();
+        }
+
+    (StatusCode::OK, [(header::CONTENT_TYPE, encoder.format_type().to_string())], buffer).into_response()
+    }
+
+/// A ClipboardStore wrapper recording latency and errors of the wrapped store.
+pub struct MeteredStore {
+    store: Box<dyn ClipboardStore>,
+    }
+impl MeteredStore {
+
+    pub fn new(store: Box<dyn ClipboardStore>) -> MeteredStore {
+        MeteredStore { store }
+        }
+
+    fn record<T>(operation: &str, started_at: Instant, result: Result<T, anyhow::Error>) -> Result<T, anyhow::Error> {
+        STORAGE_OPERATION_DURATION.observe(started_at.elapsed().as_secs_f64());
+
+        if result.is_err() {
+            STORAGE_ERRORS.with_label_values(&[operation]).inc();
+            }
+
+        result
+        }
+    }
+#[async_trait]
+impl ClipboardStore for MeteredStore {
+
+    async fn get(&self, id: &str) -> Result<Option<Vec<u8>>, anyhow::Error> {
+        let started_at=Instant::now();
+        MeteredStore::record("get", started_at, self.store.get(id).await)
+        }
+    async fn set(&self, id: &str, content: &[u8], ttl: Duration) -> Result<(), anyhow::Error> {
+        let started_at=Instant::now();
+        MeteredStore::record("set", started_at, self.store.set(id, content, ttl).await)
+        }
+    async fn delete(&self, id: &str) -> Result<(), anyhow::Error> {
+        let started_at=Instant::now();
+        MeteredStore::record("delete", started_at, self.store.delete(id).await)
+        }
+    async fn expire(&self, id: &str, ttl: Duration) -> Result<bool, anyhow::Error> {
+        let started_at=Instant::now();
+        MeteredStore::record("expire", started_at, self.store.expire(id, ttl).await)
+        }
+    async fn list(&self) -> Result<Vec<StoredClipboard>, anyhow::Error> {
+        let started_at=Instant::now();
+        MeteredStore::record("list", started_at, self.store.list().await)
+        }
+    async fn ping(&self) -> Result<(), anyhow::Error> {
+        let started_at=Instant::now();
+        MeteredStore::record("ping", started_at, self.store.ping().await)
+        }
+    async fn purge_expired(&self) -> Result<usize, anyhow::Error> {
+        let started_at=Instant::now();
+        MeteredStore::record("purge_expired", started_at, self.store.purge_expired().await)
+        }
+    async fn get_stream(&self, id: &str) -> Result<Option<(usize, ContentStream)>, anyhow::Error> {
+        let started_at=Instant::now();
+        MeteredStore::record("get_stream", started_at, self.store.get_stream(id).await)
+        }
+    async fn size(&self, id: &str) -> Result<Option<usize>, anyhow::Error> {
+        let started_at=Instant::now();
+        MeteredStore::record("size", started_at, self.store.size(id).await)
+        }
+    async fn set_stream(&self, id: &str, content: ContentStream, ttl: Duration) -> Result<usize, anyhow::Error> {
+        let started_at=Instant::now();
+        MeteredStore::record("set_stream", started_at, self.store.set_stream(id, content, ttl).await)
+        }
+    }
+
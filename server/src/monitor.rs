@@ -0,0 +1,701 @@
+/*
+* Copyright (C) 2023 Rastislav Kish
+*
+* This program is free software: you can redistribute it and/or modify
+* it under the terms of the GNU General Public License as published by
+* the Free Software Foundation, version 3.
+*
+* This program is distributed in the hope that it will be useful,
+* but WITHOUT ANY WARRANTY; without even the implied warranty of
+* MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+* GNU General Public License for more details.
+*
+* You should have received a copy of the GNU General Public License
+* along with this program. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fmt;
+use std::str::FromStr;
+use std::time::{Instant, Duration};
+
+use anyhow::bail;
+
+use crate::manifest;
+use crate::metrics::{COLLECTED_CLIPBOARDS, EVICTIONS, GARBAGE_COLLECTIONS};
+use crate::storage::StoredClipboard;
+use crate::{CLIPBOARD_CONTENT_EXPIRATION_TIME, EVICTION_POLICY, ID_QUOTAS, MAX_CLIPBOARD_COUNT, MAX_USED_SPACE};
+
+/// The maximum number of uploads of a clipboard ID whose parts are being uploaded, but whose manifest wasn't published yet.
+/// Abandoned uploads count until their parts expire, so the limit leaves room for a few failed attempts.
+pub const MAX_UNPUBLISHED_UPLOADS: usize=8;
+
+/// Determines what happens when a clipboard doesn't fit into the server even after the expired ones were collected.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum EvictionPolicy {
+    /// Refuses the new clipboard.
+    Reject,
+    /// Evicts the least recently written clipboards until the new one fits.
+    Oldest,
+    /// Evicts the largest clipboards until the new one fits.
+    Largest,
+    }
+impl FromStr for EvictionPolicy {
+    type Err=anyhow::Error;
+
+    fn from_str(policy: &str) -> Result<EvictionPolicy, anyhow::Error> {
+        match policy.to_lowercase().as_str() {
+            "reject" => Ok(EvictionPolicy::Reject),
+            "oldest" => Ok(EvictionPolicy::Oldest),
+            "largest" => Ok(EvictionPolicy::Largest),
+            _ => bail!("Unknown eviction policy {policy}"),
+            }
+        }
+    }
+
+/// The reason why a clipboard could not be reserved.
+#[derive(Debug)]
+pub enum ReservationError {
+    /// There is no room for the clipboard on the server.
+    StorageFull,
+    /// The clipboard is larger than the size quota of its ID.
+    SizeQuotaExceeded(usize),
+    /// The ID has used up its write quota, the duration tells when it gets renewed.
+    WriteQuotaExceeded(Duration),
+    /// The ID has too many unpublished uploads of multi-part clipboards.
+    TooManyUploads,
+    }
+impl fmt::Display for ReservationError {
+
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReservationError::StorageFull => write!(f, "Storage full"),
+            ReservationError::SizeQuotaExceeded(max_size) => write!(f, "Clipboard exceeds the size quota of {max_size} bytes"),
+            ReservationError::WriteQuotaExceeded(_) => write!(f, "Write quota exceeded"),
+            ReservationError::TooManyUploads => write!(f, "Too many unpublished uploads, at most {MAX_UNPUBLISHED_UPLOADS} are allowed"),
+            }
+        }
+    }
+impl std::error::Error for ReservationError {}
+
+/// Counts writes of a clipboard ID within a write quota period.
+struct WriteWindow {
+    started_at: Instant,
+    count: u32,
+    }
+
+/// The parts of a multi-part clipboard uploaded under one upload ID.
+#[derive(Default)]
+struct Upload {
+    part_ids: HashSet<String>,
+    size: usize,
+    published: bool,
+    }
+
+/// Clipboards evicted together, so no clipboard is left behind broken.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+enum EvictionGroup {
+    /// A clipboard, together with the parts listed in its manifest if it's a multi-part clipboard.
+    Clipboard(String),
+    /// Parts of an upload which isn't the published content of its clipboard, by clipboard ID and upload ID.
+    Upload(String, String),
+    }
+
+#[derive(Clone)]
+pub struct Clipboard {
+    created_at: Instant,
+    size: usize,
+    generation: u64,
+    }
+impl Clipboard {
+
+    pub fn new(created_at: Instant, size: usize) -> Clipboard {
+        Clipboard { created_at, size, generation: 0 }
+        }
+
+    pub fn created_at(&self) -> Instant {
+        self.created_at
+        }
+    pub fn size(&self) -> usize {
+        self.size
+        }
+
+    pub fn valid(&self) -> bool {
+        let current_time=Instant::now();
+
+        if current_time.duration_since(self.created_at)>=*CLIPBOARD_CONTENT_EXPIRATION_TIME {
+            return false;
+            }
+
+        true
+        }
+    }
+
+/// Space reserved for a clipboard by the clipboard monitor.
+/// The generation tells the reservation apart from later reservations of the same clipboard, so releasing it never drops a newer one.
+pub struct Reservation {
+    id: String,
+    generation: u64,
+    previous: Option<Clipboard>,
+    evicted_clipboards: Vec<String>,
+    }
+impl Reservation {
+
+    /// IDs of clipboards that were garbage collected or evicted in order to make room, to be deleted from the storage.
+    pub fn evicted_clipboards(&self) -> &[String] {
+        &self.evicted_clipboards
+        }
+    }
+
+/// Keeps track of the clipboards in the storage, enforcing the count and space limits of the server.
+/// Since all clipboards share the same expiration time, the expiry queue orders them by creation time, so the expired ones can be collected from its front without scanning the whole map.
+/// Parts of multi-part clipboards are monitored as separate clipboards, grouped into uploads by clipboard ID and upload ID.
+/// Published multi-part clipboards map to the uploads listed in their manifests, so they can be evicted as a whole.
+pub struct ClipboardMonitor {
+    clipboards: HashMap<String, Clipboard>,
+    expiry_queue: BTreeSet<(Instant, String)>,
+    uploads: HashMap<String, HashMap<String, Upload>>,
+    manifests: HashMap<String, String>,
+    write_windows: HashMap<String, WriteWindow>,
+    total_used_space: usize,
+    next_generation: u64,
+    }
+impl ClipboardMonitor {
+
+    pub fn new() -> ClipboardMonitor {
+        let clipboards=HashMap::with_capacity(*MAX_CLIPBOARD_COUNT);
+        let expiry_queue=BTreeSet::new();
+        let uploads=HashMap::new();
+        let manifests=HashMap::new();
+        let write_windows=HashMap::new();
+        let total_used_space=0_usize;
+        let next_generation=1_u64;
+
+        ClipboardMonitor { clipboards, expiry_queue, uploads, manifests, write_windows, total_used_space, next_generation }
+        }
+
+    pub fn clipboard_count(&self) -> usize {
+        self.clipboards.len()
+        }
+    pub fn total_used_space(&self) -> usize {
+        self.total_used_space
+        }
+
+    /// Whether the count or space limit of the server has been reached.
+    pub fn full(&self) -> bool {
+        self.clipboards.len()>=*MAX_CLIPBOARD_COUNT || self.total_used_space>=*MAX_USED_SPACE
+        }
+
+    /// Reserves space for a clipboard, evicting other clipboards if needed to make room.
+    pub fn reserve_clipboard(&mut self, id: &str, size: usize) -> Result<Reservation, ReservationError> {
+        self.check_quota(id, size)?;
+
+        let reservation=self.reserve_space(id, size, &[])?;
+        self.count_write(id);
+
+        Ok(reservation)
+        }
+
+    /// Reserves space for the manifest of a multi-part clipboard, marking its upload as published, see reserve_clipboard.
+    /// The quota of the clipboard is checked against the total size of its parts rather than the size of the manifest.
+    pub fn reserve_manifest(&mut self, id: &str, upload_id: &str, size: usize, content_size: usize) -> Result<Reservation, ReservationError> {
+        self.check_quota(id, content_size)?;
+
+        // The parts of the published upload must not make room for its own manifest
+        let upload_group=EvictionGroup::Upload(id.to_string(), upload_id.to_string());
+        let reservation=self.reserve_space(id, size, &[upload_group])?;
+        self.count_write(id);
+
+        if let Some(upload)=self.uploads.get_mut(id).and_then(|uploads| uploads.get_mut(upload_id)) {
+            upload.published=true;
+            self.manifests.insert(id.to_string(), upload_id.to_string());
+            }
+
+        Ok(reservation)
+        }
+
+    /// Reserves space for a part of a multi-part clipboard, see reserve_clipboard.
+    /// The parts uploaded so far are checked against the size quota as a whole, while the write is counted once the clipboard gets published by reserve_manifest.
+    /// Opening a new upload fails if the ID has MAX_UNPUBLISHED_UPLOADS unpublished uploads already.
+    pub fn reserve_part(&mut self, id: &str, upload_id: &str, number: usize, size: usize) -> Result<Reservation, ReservationError> {
+        let part_id=manifest::part_id(id, upload_id, number);
+
+        let upload=self.uploads.get(id).and_then(|uploads| uploads.get(upload_id));
+        if upload.is_none() && self.unpublished_upload_count(id)>=MAX_UNPUBLISHED_UPLOADS {
+            return Err(ReservationError::TooManyUploads);
+            }
+
+        // A repeated upload of a part replaces the previous attempt
+        let previous_size=self.clipboards.get(&part_id).map_or(0, |part| part.size());
+        let upload_size=upload.map_or(0, |upload| upload.size)-previous_size+size;
+        self.check_quota(id, upload_size)?;
+
+        self.reserve_space(&part_id, size, &[])
+        }
+
+    /// Gives back the space of a reservation whose clipboard failed to be written, restoring the clipboard it was going to replace.
+    /// Nothing happens if the clipboard has been reserved anew in the meantime.
+    pub fn release(&mut self, reservation: &Reservation) {
+        if self.clipboards.get(&reservation.id).is_none_or(|clipboard| clipboard.generation!=reservation.generation) {
+            return;
+            }
+
+        self.remove_clipboard(&reservation.id);
+        if let Some(previous)=&reservation.previous {
+            self.insert_clipboard(&reservation.id, previous.clone());
+            }
+        }
+
+    fn unpublished_upload_count(&self, id: &str) -> usize {
+        self.uploads.get(id)
+        .map_or(0, |uploads| uploads.values().filter(|upload| !upload.published).count())
+        }
+
+    /// Restarts the expiration of monitored clipboards, whose time to live has been set anew in the storage.
+    /// Done for the parts of a multi-part clipboard once its manifest gets published, so they expire together with it.
+    pub fn refresh_clipboards(&mut self, ids: &[String]) {
+        let current_time=Instant::now();
+
+        for id in ids {
+            if let Some(clipboard)=self.remove_clipboard(id) {
+                self.insert_clipboard(id, Clipboard::new(current_time, clipboard.size()));
+                }
+            }
+        }
+
+    /// Makes room for a clipboard by collecting and evicting other clipboards, never the eviction group of the clipboard itself or any of the protected groups.
+    fn reserve_space(&mut self, id: &str, size: usize, protected_groups: &[EvictionGroup]) -> Result<Reservation, ReservationError> {
+        let mut collected_clipboards=Vec::new();
+
+        if !self.clipboard_fits(id, size) {
+            collected_clipboards=self.garbage_collect();
+
+            if !self.clipboard_fits(id, size) {
+                // No amount of evictions helps if the clipboard doesn't fit even into an empty server
+                if *EVICTION_POLICY==EvictionPolicy::Reject || size>*MAX_USED_SPACE || *MAX_CLIPBOARD_COUNT==0 {
+                    return Err(ReservationError::StorageFull);
+                    }
+
+                let mut protected_groups=protected_groups.to_vec();
+                protected_groups.push(self.eviction_group(id));
+
+                while !self.clipboard_fits(id, size) {
+                    let Some(evicted_group)=self.eviction_candidate(*EVICTION_POLICY, &protected_groups) else {
+                        return Err(ReservationError::StorageFull);
+                        };
+
+                    for evicted_id in self.eviction_group_members(&evicted_group) {
+                        self.remove_clipboard(&evicted_id);
+
+                        tracing::info!("Evicted clipboard {evicted_id} to make room for clipboard {id}");
+                        EVICTIONS.inc();
+                        collected_clipboards.push(evicted_id);
+                        }
+                    }
+                }
+            }
+
+        let previous=self.clipboards.get(id).cloned();
+        let generation=self.next_generation;
+        self.next_generation+=1;
+        self.insert_clipboard(id, Clipboard { generation, ..Clipboard::new(Instant::now(), size) });
+
+        Ok(Reservation { id: id.to_string(), generation, previous, evicted_clipboards: collected_clipboards })
+        }
+
+    /// Replaces the monitored clipboards with the ones actually present in the storage.
+    /// Clipboards reserved after the since instant are kept, as the listing may have been taken before they were written.
+    pub fn rebuild(&mut self, stored_clipboards: Vec<StoredClipboard>, since: Instant) {
+        let current_time=Instant::now();
+
+        let recent_clipboards: Vec<(String, Clipboard)>=self.clipboards.iter()
+        .filter(|(_, clipboard)| clipboard.created_at()>=since)
+        .map(|(id, clipboard)| (id.clone(), clipboard.clone()))
+        .collect();
+
+        // Uploads unknown to the monitor, i.e. after a restart, are taken for published, so they don't hold up new uploads of their IDs
+        let unpublished_uploads: HashSet<(String, String)>=self.uploads.iter()
+        .flat_map(|(id, uploads)| uploads.iter()
+            .filter(|(_, upload)| !upload.published)
+            .map(|(upload_id, _)| (id.clone(), upload_id.clone())))
+        .collect();
+
+        let manifests=std::mem::take(&mut self.manifests);
+
+        self.clipboards.clear();
+        self.expiry_queue.clear();
+        self.uploads.clear();
+        self.total_used_space=0;
+
+        for stored_clipboard in stored_clipboards {
+            // Reconstructing the creation time from the remaining time to live, records without expiration are treated as fresh
+            let age=match stored_clipboard.ttl() {
+                Some(ttl) => CLIPBOARD_CONTENT_EXPIRATION_TIME.saturating_sub(ttl),
+                None => Duration::ZERO,
+                };
+            let created_at=current_time.checked_sub(age).unwrap_or(current_time);
+
+            self.insert_clipboard(stored_clipboard.id(), Clipboard::new(created_at, stored_clipboard.size()));
+            }
+
+        for (id, clipboard) in recent_clipboards {
+            self.insert_clipboard(&id, clipboard);
+            }
+
+        for (id, uploads) in &mut self.uploads {
+            for (upload_id, upload) in uploads {
+                if !unpublished_uploads.contains(&(id.clone(), upload_id.clone())) {
+                    upload.published=true;
+                    }
+                }
+            }
+
+        // Only the monitor knows which upload a manifest lists, after a restart the parts of multi-part clipboards get evicted separately
+        self.manifests=manifests.into_iter()
+        .filter(|(id, upload_id)| self.clipboards.contains_key(id) && self.uploads.get(id).is_some_and(|uploads| uploads.contains_key(upload_id)))
+        .collect();
+        }
+
+    /// Removes expired clipboards, returning their IDs.
+    /// Only the expired front of the expiry queue is visited, so this is cheap enough to be run often.
+    pub fn garbage_collect(&mut self) -> Vec<String> {
+        let mut collected_clipboards=Vec::new();
+
+        while let Some((_, id))=self.expiry_queue.first() {
+            if self.clipboards.get(id).is_some_and(|clipboard| clipboard.valid()) {
+                break;
+                }
+
+            let (_, id)=self.expiry_queue.pop_first().unwrap();
+            self.remove_clipboard(&id);
+
+            collected_clipboards.push(id);
+            }
+
+        GARBAGE_COLLECTIONS.inc();
+        COLLECTED_CLIPBOARDS.inc_by(collected_clipboards.len() as u64);
+
+        self.write_windows.retain(|id, write_window| {
+            ID_QUOTAS.get(id).max_writes()
+            .is_some_and(|(_, period)| write_window.started_at.elapsed()<period)
+            });
+
+        collected_clipboards
+        }
+
+    /// Checks the quota of the clipboard ID, opening a new write window if the previous one has passed.
+    fn check_quota(&mut self, id: &str, size: usize) -> Result<(), ReservationError> {
+        let quota=ID_QUOTAS.get(id);
+
+        if let Some(max_size)=quota.max_size() {
+            if size>max_size {
+                return Err(ReservationError::SizeQuotaExceeded(max_size));
+                }
+            }
+
+        if let Some((max_writes, period))=quota.max_writes() {
+            let current_time=Instant::now();
+            let write_window=self.write_windows.entry(id.to_string())
+            .or_insert(WriteWindow { started_at: current_time, count: 0 });
+
+            let elapsed=current_time.duration_since(write_window.started_at);
+            if elapsed>=period {
+                *write_window=WriteWindow { started_at: current_time, count: 0 };
+                }
+            else if write_window.count>=max_writes {
+                return Err(ReservationError::WriteQuotaExceeded(period-elapsed));
+                }
+            }
+
+        Ok(())
+        }
+
+    /// Counts a write of the clipboard ID into its write window opened by check_quota.
+    fn count_write(&mut self, id: &str) {
+        if let Some(write_window)=self.write_windows.get_mut(id) {
+            write_window.count+=1;
+            }
+        }
+
+    /// Picks the group of clipboards to evict according to the eviction policy, skipping the protected groups.
+    /// The groups are ordered by their oldest clipboard, or by their total size.
+    fn eviction_candidate(&self, policy: EvictionPolicy, protected_groups: &[EvictionGroup]) -> Option<EvictionGroup> {
+        match policy {
+            EvictionPolicy::Reject => None,
+            EvictionPolicy::Oldest => self.expiry_queue.iter()
+                .map(|(_, id)| self.eviction_group(id))
+                .find(|group| !protected_groups.contains(group)),
+            EvictionPolicy::Largest => {
+                let mut group_sizes: HashMap<EvictionGroup, usize>=HashMap::new();
+                for (id, clipboard) in &self.clipboards {
+                    *group_sizes.entry(self.eviction_group(id)).or_default()+=clipboard.size();
+                    }
+
+                group_sizes.into_iter()
+                .filter(|(group, _)| !protected_groups.contains(group))
+                .max_by_key(|(_, size)| *size)
+                .map(|(group, _)| group)
+                },
+            }
+        }
+
+    /// The eviction group of a clipboard, parts listed in the manifest of their clipboard belong to the clipboard.
+    fn eviction_group(&self, id: &str) -> EvictionGroup {
+        match manifest::parse_part_id(id) {
+            Some((clipboard_id, upload_id)) if self.manifests.get(clipboard_id).is_some_and(|published_upload_id| published_upload_id==upload_id) => {
+                EvictionGroup::Clipboard(clipboard_id.to_string())
+                },
+            Some((clipboard_id, upload_id)) => EvictionGroup::Upload(clipboard_id.to_string(), upload_id.to_string()),
+            None => EvictionGroup::Clipboard(id.to_string()),
+            }
+        }
+
+    /// IDs of the monitored clipboards of an eviction group.
+    fn eviction_group_members(&self, group: &EvictionGroup) -> Vec<String> {
+        let mut members=Vec::new();
+
+        let upload=match group {
+            EvictionGroup::Clipboard(id) => {
+                if self.clipboards.contains_key(id) {
+                    members.push(id.clone());
+                    }
+
+                self.manifests.get(id).and_then(|upload_id| self.uploads.get(id)?.get(upload_id))
+                },
+            EvictionGroup::Upload(id, upload_id) => self.uploads.get(id).and_then(|uploads| uploads.get(upload_id)),
+            };
+        if let Some(upload)=upload {
+            members.extend(upload.part_ids.iter().cloned());
+            }
+
+        members
+        }
+
+    fn insert_clipboard(&mut self, id: &str, clipboard: Clipboard) {
+        self.remove_clipboard(id);
+
+        if let Some((clipboard_id, upload_id))=manifest::parse_part_id(id) {
+            let upload=self.uploads.entry(clipboard_id.to_string()).or_default()
+            .entry(upload_id.to_string()).or_default();
+            upload.part_ids.insert(id.to_string());
+            upload.size+=clipboard.size();
+            }
+
+        self.total_used_space+=clipboard.size();
+        self.expiry_queue.insert((clipboard.created_at(), id.to_string()));
+        self.clipboards.insert(id.to_string(), clipboard);
+        }
+    fn remove_clipboard(&mut self, id: &str) -> Option<Clipboard> {
+        let clipboard=self.clipboards.remove(id)?;
+
+        if let Some((clipboard_id, upload_id))=manifest::parse_part_id(id) {
+            if let Some(uploads)=self.uploads.get_mut(clipboard_id) {
+                if let Some(upload)=uploads.get_mut(upload_id) {
+                    upload.part_ids.remove(id);
+                    upload.size-=clipboard.size();
+
+                    if upload.part_ids.is_empty() {
+                        uploads.remove(upload_id);
+                        }
+                    }
+                if uploads.is_empty() {
+                    self.uploads.remove(clipboard_id);
+                    }
+                }
+            }
+        else {
+            self.manifests.remove(id);
+            }
+
+        self.total_used_space-=clipboard.size();
+        self.expiry_queue.remove(&(clipboard.created_at(), id.to_string()));
+
+        Some(clipboard)
+        }
+    fn clipboard_fits(&self, id: &str, size: usize) -> bool {
+        let (count, used_space)=match self.clipboards.get(id) {
+            Some(clipboard) => (self.clipboards.len(), self.total_used_space-clipboard.size()+size),
+            None => (self.clipboards.len()+1, self.total_used_space+size),
+            };
+
+        count<=*MAX_CLIPBOARD_COUNT && used_space<=*MAX_USED_SPACE
+        }
+    }
+impl Default for ClipboardMonitor {
+
+    fn default() -> ClipboardMonitor {
+        ClipboardMonitor::new()
+        }
+    }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reserves_clipboards() {
+        let mut monitor=ClipboardMonitor::new();
+
+        assert!(monitor.reserve_clipboard("a", 100).unwrap().evicted_clipboards().is_empty());
+        assert!(monitor.reserve_clipboard("b", 50).unwrap().evicted_clipboards().is_empty());
+        assert_eq!(monitor.clipboard_count(), 2);
+        assert_eq!(monitor.total_used_space(), 150);
+
+        // Rewriting a clipboard replaces its size instead of adding to it
+        monitor.reserve_clipboard("a", 10).unwrap();
+        assert_eq!(monitor.clipboard_count(), 2);
+        assert_eq!(monitor.total_used_space(), 60);
+        }
+
+    #[test]
+    fn rejects_clipboards_larger_than_the_server() {
+        let mut monitor=ClipboardMonitor::new();
+
+        let result=monitor.reserve_clipboard("a", *MAX_USED_SPACE+1);
+        assert!(matches!(result, Err(ReservationError::StorageFull)));
+        assert_eq!(monitor.clipboard_count(), 0);
+        }
+
+    #[test]
+    fn garbage_collects_expired_clipboards() {
+        let mut monitor=ClipboardMonitor::new();
+        monitor.rebuild(vec![
+            StoredClipboard::new("expired", 100, Some(Duration::ZERO)),
+            StoredClipboard::new("valid", 50, Some(*CLIPBOARD_CONTENT_EXPIRATION_TIME)),
+            ], Instant::now());
+
+        assert_eq!(monitor.garbage_collect(), vec!["expired".to_string()]);
+        assert_eq!(monitor.clipboard_count(), 1);
+        assert_eq!(monitor.total_used_space(), 50);
+        assert!(monitor.garbage_collect().is_empty());
+        }
+
+    #[test]
+    fn rebuilds_from_the_storage() {
+        let mut monitor=ClipboardMonitor::new();
+        monitor.reserve_clipboard("gone", 100).unwrap();
+        let since=Instant::now();
+        monitor.reserve_clipboard("recent", 10).unwrap();
+
+        monitor.rebuild(vec![
+            StoredClipboard::new("stored", 20, Some(Duration::from_secs(1))),
+            StoredClipboard::new("persistent", 30, None),
+            ], since);
+
+        // The clipboard reserved before the listing is dropped, the one reserved during it is kept
+        assert_eq!(monitor.clipboard_count(), 3);
+        assert_eq!(monitor.total_used_space(), 60);
+        assert!(monitor.garbage_collect().is_empty());
+        }
+
+    #[test]
+    fn limits_unpublished_uploads() {
+        let mut monitor=ClipboardMonitor::new();
+        for upload in 0..MAX_UNPUBLISHED_UPLOADS {
+            monitor.reserve_part("a", &format!("upload{upload}"), 0, 10).unwrap();
+            }
+
+        assert!(matches!(monitor.reserve_part("a", "another", 0, 10), Err(ReservationError::TooManyUploads)));
+        assert!(monitor.reserve_part("b", "another", 0, 10).is_ok());
+        assert!(monitor.reserve_part("a", "upload0", 1, 10).is_ok());
+
+        monitor.reserve_manifest("a", "upload0", 10, 20).unwrap();
+        assert!(monitor.reserve_part("a", "another", 0, 10).is_ok());
+        }
+
+    #[test]
+    fn takes_stored_uploads_for_published() {
+        let mut monitor=ClipboardMonitor::new();
+        let stored_parts=(0..MAX_UNPUBLISHED_UPLOADS)
+        .map(|upload| StoredClipboard::new(&manifest::part_id("a", &format!("upload{upload}"), 0), 10, None))
+        .collect();
+        monitor.rebuild(stored_parts, Instant::now());
+
+        assert!(monitor.reserve_part("a", "another", 0, 10).is_ok());
+        }
+
+    /// A monitor with a single-part clipboard of 10 bytes, a published multi-part clipboard of 205 bytes and an unpublished upload of 50 bytes.
+    fn monitor_with_uploads() -> ClipboardMonitor {
+        let mut monitor=ClipboardMonitor::new();
+        monitor.reserve_clipboard("single", 10).unwrap();
+        monitor.reserve_part("multi", "published", 0, 100).unwrap();
+        monitor.reserve_part("multi", "published", 1, 100).unwrap();
+        monitor.reserve_manifest("multi", "published", 5, 200).unwrap();
+        monitor.reserve_part("other", "unpublished", 0, 50).unwrap();
+
+        monitor
+        }
+
+    #[test]
+    fn evicts_multi_part_clipboards_as_a_whole() {
+        let monitor=monitor_with_uploads();
+
+        let group=monitor.eviction_candidate(EvictionPolicy::Largest, &[]).unwrap();
+        assert_eq!(group, EvictionGroup::Clipboard("multi".to_string()));
+
+        let mut members=monitor.eviction_group_members(&group);
+        members.sort();
+        assert_eq!(members, vec!["multi", "multi.published.0", "multi.published.1"]);
+        }
+
+    #[test]
+    fn never_evicts_protected_groups() {
+        let monitor=monitor_with_uploads();
+        let upload_group=monitor.eviction_group(&manifest::part_id("other", "unpublished", 1));
+        assert_eq!(upload_group, EvictionGroup::Upload("other".to_string(), "unpublished".to_string()));
+
+        let protected_groups=[monitor.eviction_group("multi"), upload_group];
+        assert_eq!(monitor.eviction_candidate(EvictionPolicy::Largest, &protected_groups), Some(EvictionGroup::Clipboard("single".to_string())));
+        assert_eq!(monitor.eviction_candidate(EvictionPolicy::Oldest, &protected_groups), Some(EvictionGroup::Clipboard("single".to_string())));
+        assert_eq!(monitor.eviction_candidate(EvictionPolicy::Reject, &[]), None);
+        }
+
+    #[test]
+    fn evicts_replaced_uploads_separately() {
+        let mut monitor=monitor_with_uploads();
+        monitor.reserve_clipboard("multi", 5).unwrap();
+
+        assert_eq!(monitor.eviction_group("multi.published.0"), EvictionGroup::Upload("multi".to_string(), "published".to_string()));
+        assert_eq!(monitor.eviction_group_members(&EvictionGroup::Clipboard("multi".to_string())), vec!["multi"]);
+        }
+
+    #[test]
+    fn refreshes_clipboards() {
+        let mut monitor=ClipboardMonitor::new();
+        monitor.rebuild(vec![StoredClipboard::new("part", 100, Some(Duration::ZERO))], Instant::now());
+
+        monitor.refresh_clipboards(&["part".to_string(), "missing".to_string()]);
+        assert!(monitor.garbage_collect().is_empty());
+        assert_eq!(monitor.clipboard_count(), 1);
+        assert_eq!(monitor.total_used_space(), 100);
+        }
+
+    #[test]
+    fn releases_reservations() {
+        let mut monitor=ClipboardMonitor::new();
+        monitor.reserve_clipboard("a", 100).unwrap();
+
+        // Releasing a failed rewrite restores the clipboard it was going to replace
+        let reservation=monitor.reserve_clipboard("a", 10).unwrap();
+        monitor.release(&reservation);
+        assert_eq!(monitor.clipboard_count(), 1);
+        assert_eq!(monitor.total_used_space(), 100);
+
+        let reservation=monitor.reserve_clipboard("b", 50).unwrap();
+        monitor.release(&reservation);
+        assert_eq!(monitor.clipboard_count(), 1);
+        assert_eq!(monitor.total_used_space(), 100);
+        }
+
+    #[test]
+    fn keeps_newer_reservations_on_release() {
+        let mut monitor=ClipboardMonitor::new();
+        let reservation=monitor.reserve_clipboard("a", 100).unwrap();
+        monitor.reserve_clipboard("a", 10).unwrap();
+
+        monitor.release(&reservation);
+        assert_eq!(monitor.clipboard_count(), 1);
+        assert_eq!(monitor.total_used_space(), 10);
+        }
+    }
+
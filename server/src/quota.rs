@@ -0,0 +1,150 @@
+/*
+* Copyright (C) 2023 Rastislav Kish
+*
+* This program is free software: you can redistribute it and/or modify
+* it under the terms of the GNU General Public License as published by
+* the Free Software Foundation, version 3.
+*
+* This program is distributed in the hope that it will be useful,
+* but WITHOUT ANY WARRANTY; without even the implied warranty of
+* MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+* GNU General Public License for more details.
+*
+* You should have received a copy of the GNU General Public License
+* along with this program. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use anyhow::{bail, Context};
+
+use crate::{parse_duration, parse_size, CLIPBOARD_ID_REGEX};
+
+/// Limits of a single clipboard ID, so one user can't consume the whole server budget.
+#[derive(Clone, Copy, Default)]
+pub struct Quota {
+    max_size: Option<usize>,
+    max_writes: Option<(u32, Duration)>,
+    }
+impl Quota {
+
+    /// Parses a quota in format <size>,<writes>, i.e. 1M,100/1H for clipboards of up to 1 MB written at most 100 times per hour.
+    /// Either part can be off to leave it unlimited.
+    pub fn parse(quota: &str) -> Result<Quota, anyhow::Error> {
+        let Some((max_size, max_writes))=quota.split_once(',') else {
+            bail!("Invalid quota {quota}, expected format <size>,<writes>");
+            };
+
+        let max_size=if max_size.eq_ignore_ascii_case("off") {
+            None
+            }
+        else {
+            Some(parse_size(max_size)?)
+            };
+
+        let max_writes=if max_writes.eq_ignore_ascii_case("off") {
+            None
+            }
+        else {
+            let Some((count, period))=max_writes.split_once('/') else {
+                bail!("Invalid write quota {max_writes}, expected format <count>/<period>");
+                };
+            let count: u32=count.parse().with_context(|| format!("Invalid write count in quota {quota}"))?;
+
+            Some((count, parse_duration(period)?))
+            };
+
+        Ok(Quota { max_size, max_writes })
+        }
+
+    pub fn max_size(&self) -> Option<usize> {
+        self.max_size
+        }
+    /// The number of writes allowed per period.
+    pub fn max_writes(&self) -> Option<(u32, Duration)> {
+        self.max_writes
+        }
+    }
+
+/// The default quota together with per-ID overrides.
+pub struct Quotas {
+    default_quota: Quota,
+    overrides: HashMap<String, Quota>,
+    }
+impl Quotas {
+
+    pub fn new(default_quota: Quota) -> Quotas {
+        Quotas { default_quota, overrides: HashMap::new() }
+        }
+
+    /// Parses per-ID overrides in format <id>=<quota>;<id>=<quota>, with quotas as in Quota::parse.
+    pub fn with_overrides(mut self, overrides: &str) -> Result<Quotas, anyhow::Error> {
+        for entry in overrides.split(';').map(|entry| entry.trim()).filter(|entry| !entry.is_empty()) {
+            let Some((id, quota))=entry.split_once('=') else {
+                bail!("Invalid quota override {entry}, expected format <id>=<quota>");
+                };
+            if !CLIPBOARD_ID_REGEX.is_match(id) {
+                bail!("Invalid clipboard ID {id} in quota override");
+                }
+
+            self.overrides.insert(id.to_string(), Quota::parse(quota)?);
+            }
+
+        Ok(self)
+        }
+
+    /// The quota of clipboard IDs without an override.
+    pub fn default_quota(&self) -> Quota {
+        self.default_quota
+        }
+
+    /// Returns the quota applying to a clipboard ID.
+    pub fn get(&self, id: &str) -> Quota {
+        self.overrides.get(id).copied().unwrap_or(self.default_quota)
+        }
+    }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_quota() {
+        let quota=Quota::parse("1M,100/1H").unwrap();
+        assert_eq!(quota.max_size(), Some(1000000));
+        assert_eq!(quota.max_writes(), Some((100, Duration::from_secs(3600))));
+        }
+
+    #[test]
+    fn parses_partially_disabled_quota() {
+        let quota=Quota::parse("off,10/1M").unwrap();
+        assert_eq!(quota.max_size(), None);
+        assert_eq!(quota.max_writes(), Some((10, Duration::from_secs(60))));
+
+        let quota=Quota::parse("5K,OFF").unwrap();
+        assert_eq!(quota.max_size(), Some(5000));
+        assert_eq!(quota.max_writes(), None);
+        }
+
+    #[test]
+    fn rejects_invalid_quota() {
+        assert!(Quota::parse("1M").is_err());
+        assert!(Quota::parse("1X,off").is_err());
+        assert!(Quota::parse("off,100").is_err());
+        assert!(Quota::parse("off,many/1H").is_err());
+        }
+
+    #[test]
+    fn applies_overrides() {
+        let id="a".repeat(32);
+        let quotas=Quotas::new(Quota::parse("1M,off").unwrap())
+        .with_overrides(&format!("{id}=2M,off;"))
+        .unwrap();
+
+        assert_eq!(quotas.get(&id).max_size(), Some(2000000));
+        assert_eq!(quotas.get(&"b".repeat(32)).max_size(), Some(1000000));
+        assert!(Quotas::new(Quota::default()).with_overrides("short=1M,off").is_err());
+        }
+    }
+
@@ -0,0 +1,172 @@
+/*
+* Copyright (C) 2023 Rastislav Kish
+*
+* This program is free software: you can redistribute it and/or modify
+* it under the terms of the GNU General Public License as published by
+* the Free Software Foundation, version 3.
+*
+* This program is distributed in the hope that it will be useful,
+* but WITHOUT ANY WARRANTY; without even the implied warranty of
+* MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+* GNU General Public License for more details.
+*
+* You should have received a copy of the GNU General Public License
+* along with this program. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Instant, Duration};
+
+use anyhow::{bail, Context};
+
+use crate::parse_duration;
+
+/// A rate limit of a number of requests per time period, with bursts up to the whole number allowed.
+#[derive(Clone, Copy)]
+pub struct RateLimit {
+    capacity: f64,
+    refill_rate: f64,
+    }
+impl RateLimit {
+
+    /// Parses a rate limit in format <count>/<duration>, i.e. 60/1M for 60 requests per minute.
+    /// Returns None for 0 or off, meaning the rate limiting is disabled.
+    pub fn parse(rate_limit: &str) -> Result<Option<RateLimit>, anyhow::Error> {
+        if rate_limit=="0" || rate_limit.eq_ignore_ascii_case("off") {
+            return Ok(None);
+            }
+
+        let Some((count, period))=rate_limit.split_once('/') else {
+            bail!("Invalid rate limit {rate_limit}");
+            };
+
+        let count: u32=count.parse().with_context(|| format!("Invalid request count in rate limit {rate_limit}"))?;
+        let period=parse_duration(period)?;
+
+        if count==0 || period.is_zero() {
+            return Ok(None);
+            }
+
+        Ok(Some(RateLimit {
+            capacity: count as f64,
+            refill_rate: count as f64/period.as_secs_f64(),
+            }))
+        }
+    }
+
+struct TokenBucket {
+    tokens: f64,
+    updated_at: Instant,
+    }
+impl TokenBucket {
+
+    fn refill(&mut self, rate_limit: &RateLimit, current_time: Instant) {
+        let elapsed=current_time.duration_since(self.updated_at).as_secs_f64();
+
+        self.tokens=(self.tokens+elapsed*rate_limit.refill_rate).min(rate_limit.capacity);
+        self.updated_at=current_time;
+        }
+    }
+
+/// A token bucket rate limiter keeping a separate bucket for every key, i.e. a client IP or a clipboard ID.
+pub struct RateLimiter {
+    rate_limit: RateLimit,
+    buckets: Mutex<HashMap<String, TokenBucket>>,
+    }
+impl RateLimiter {
+
+    pub fn new(rate_limit: RateLimit) -> RateLimiter {
+        let buckets=Mutex::new(HashMap::new());
+
+        RateLimiter { rate_limit, buckets }
+        }
+
+    /// Takes a token from the bucket of the key.
+    /// If the bucket is empty, returns the time after which a token will be available again.
+    pub fn check(&self, key: &str) -> Result<(), Duration> {
+        let current_time=Instant::now();
+        let mut buckets=self.buckets.lock().unwrap();
+
+        let bucket=buckets.entry(key.to_string())
+        .or_insert(TokenBucket { tokens: self.rate_limit.capacity, updated_at: current_time });
+        bucket.refill(&self.rate_limit, current_time);
+
+        if bucket.tokens>=1.0 {
+            bucket.tokens-=1.0;
+            return Ok(());
+            }
+
+        Err(Duration::from_secs_f64((1.0-bucket.tokens)/self.rate_limit.refill_rate))
+        }
+
+    /// Drops buckets which got refilled completely, as they're no different from new ones.
+    pub fn purge_idle(&self) {
+        let current_time=Instant::now();
+        let mut buckets=self.buckets.lock().unwrap();
+
+        buckets.retain(|_, bucket| {
+            bucket.refill(&self.rate_limit, current_time);
+
+            bucket.tokens<self.rate_limit.capacity
+            });
+        }
+    }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_rate_limit() {
+        let rate_limit=RateLimit::parse("60/1M").unwrap().unwrap();
+        assert_eq!(rate_limit.capacity, 60.0);
+        assert_eq!(rate_limit.refill_rate, 1.0);
+        }
+
+    #[test]
+    fn parses_disabled_rate_limit() {
+        assert!(RateLimit::parse("0").unwrap().is_none());
+        assert!(RateLimit::parse("off").unwrap().is_none());
+        assert!(RateLimit::parse("0/1M").unwrap().is_none());
+        assert!(RateLimit::parse("10/0S").unwrap().is_none());
+        }
+
+    #[test]
+    fn rejects_invalid_rate_limit() {
+        assert!(RateLimit::parse("60").is_err());
+        assert!(RateLimit::parse("many/1M").is_err());
+        assert!(RateLimit::parse("60/1X").is_err());
+        }
+
+    #[test]
+    fn allows_bursts_up_to_the_limit() {
+        let rate_limiter=RateLimiter::new(RateLimit::parse("3/1H").unwrap().unwrap());
+
+        for _ in 0..3 {
+            assert!(rate_limiter.check("a").is_ok());
+            }
+
+        let retry_after=rate_limiter.check("a").unwrap_err();
+        assert!(retry_after>Duration::from_secs(1190) && retry_after<=Duration::from_secs(1200));
+        }
+
+    #[test]
+    fn keeps_separate_buckets_per_key() {
+        let rate_limiter=RateLimiter::new(RateLimit::parse("1/1H").unwrap().unwrap());
+
+        assert!(rate_limiter.check("a").is_ok());
+        assert!(rate_limiter.check("a").is_err());
+        assert!(rate_limiter.check("b").is_ok());
+        }
+
+    #[test]
+    fn purges_only_idle_buckets() {
+        let rate_limiter=RateLimiter::new(RateLimit::parse("1/1H").unwrap().unwrap());
+        rate_limiter.check("a").unwrap();
+        rate_limiter.purge_idle();
+
+        assert_eq!(rate_limiter.buckets.lock().unwrap().len(), 1);
+        }
+    }
+
@@ -0,0 +1,843 @@
+/*
+* Copyright (C) 2023 Rastislav Kish
+*
+* This program is free software: you can redistribute it and/or modify
+* it under the terms of the GNU General Public License as published by
+* the Free Software Foundation, version 3.
+*
+* This program is distributed in the hope that it will be useful,
+* but WITHOUT ANY WARRANTY; without even the implied warranty of
+* MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+* GNU General Public License for more details.
+*
+* You should have received a copy of the GNU General Public License
+* along with this program. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, ErrorKind, SeekFrom};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Instant, Duration, SystemTime, UNIX_EPOCH};
+
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+use tokio::sync::{Mutex, OnceCell};
+
+use async_trait::async_trait;
+use axum::body::Bytes;
+use futures_util::stream::{self, BoxStream, StreamExt, TryStreamExt};
+use tokio_util::io::ReaderStream;
+use redis::aio::ConnectionManager;
+use redis::AsyncCommands;
+
+/// An error of a store unable to reach its backend, as opposed to a failed operation.
+/// Lets the handlers tell the clients the service is temporarily unavailable rather than broken.
+#[derive(Debug)]
+pub struct BackendUnavailable(String);
+impl fmt::Display for BackendUnavailable {
+
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Storage backend unavailable. {}", self.0)
+        }
+    }
+impl std::error::Error for BackendUnavailable {}
+
+/// Clipboard content transferred in chunks, so large clipboards don't have to be held in memory as a whole.
+pub type ContentStream=BoxStream<'static, Result<Bytes, io::Error>>;
+
+/// A clipboard record as reported by ClipboardStore::list.
+/// Carries only the metadata needed for space accounting and garbage collection, not the content itself.
+pub struct StoredClipboard {
+    id: String,
+    size: usize,
+    ttl: Option<Duration>,
+    }
+impl StoredClipboard {
+
+    pub fn new(id: &str, size: usize, ttl: Option<Duration>) -> StoredClipboard {
+        StoredClipboard { id: id.to_string(), size, ttl }
+        }
+
+    pub fn id(&self) -> &str {
+        &self.id
+        }
+    pub fn size(&self) -> usize {
+        self.size
+        }
+    /// The remaining time to live of the record, None if the record does not expire.
+    pub fn ttl(&self) -> Option<Duration> {
+        self.ttl
+        }
+    }
+
+/// A backend holding the content of shared clipboards.
+/// The content is opaque to the store, it's the responsibility of the handlers to validate it.
+#[async_trait]
+pub trait ClipboardStore: Send+Sync {
+
+    /// Gets the content of a clipboard, None if the clipboard does not exist or has expired.
+    async fn get(&self, id: &str) -> Result<Option<Vec<u8>>, anyhow::Error>;
+    /// Sets the content of a clipboard, which expires after the given time to live.
+    async fn set(&self, id: &str, content: &[u8], ttl: Duration) -> Result<(), anyhow::Error>;
+    /// Deletes a clipboard. Deleting a non-existent clipboard is not an error.
+    async fn delete(&self, id: &str) -> Result<(), anyhow::Error>;
+    /// Sets a new time to live of a clipboard, counted from now, returning false if the clipboard does not exist or has expired.
+    async fn expire(&self, id: &str, ttl: Duration) -> Result<bool, anyhow::Error>;
+    /// Lists all currently stored clipboards.
+    async fn list(&self) -> Result<Vec<StoredClipboard>, anyhow::Error>;
+    /// Checks whether the store is reachable and able to serve requests.
+    async fn ping(&self) -> Result<(), anyhow::Error>;
+    /// Removes expired clipboards, returning their count.
+    /// Only needed for stores that can't expire their content on their own, the default implementation does nothing.
+    async fn purge_expired(&self) -> Result<usize, anyhow::Error> {
+        Ok(0)
+        }
+    /// Gets the content of a clipboard as a stream together with its size, None if the clipboard does not exist or has expired.
+    /// The default implementation reads the whole content with get, stores able to read it in parts should override it.
+    async fn get_stream(&self, id: &str) -> Result<Option<(usize, ContentStream)>, anyhow::Error> {
+        let Some(content)=self.get(id).await? else {
+            return Ok(None);
+            };
+
+        let size=content.len();
+        let content_stream=stream::once(async move { Ok(Bytes::from(content)) }).boxed();
+
+        Ok(Some((size, content_stream)))
+        }
+    /// Gets the size of a clipboard without its content, None if the clipboard does not exist or has expired.
+    /// The default implementation opens the clipboard with get_stream, stores able to tell the size directly should override it.
+    async fn size(&self, id: &str) -> Result<Option<usize>, anyhow::Error> {
+        Ok(self.get_stream(id).await?.map(|(size, _)| size))
+        }
+    /// Sets the content of a clipboard from a stream, returning the number of stored bytes.
+    /// The clipboard must not be visible until the stream is consumed completely, a failed stream leaves the previous content in place.
+    /// The default implementation collects the stream and stores it with set, stores able to write in parts should override it.
+    async fn set_stream(&self, id: &str, mut content: ContentStream, ttl: Duration) -> Result<usize, anyhow::Error> {
+        let mut data=Vec::new();
+        while let Some(chunk)=content.try_next().await? {
+            data.extend_from_slice(&chunk);
+            }
+
+        self.set(id, &data, ttl).await?;
+
+        Ok(data.len())
+        }
+    }
+
+/// A ClipboardStore keeping the clipboards in Redis under clipboard::<id> keys.
+/// All requests share a single multiplexed connection, which gets re-established automatically when lost.
+/// Streamed content is transferred in chunks of CHUNK_SIZE bytes, uploads are appended to a clipboard_upload::<number> key, which replaces the clipboard once complete.
+pub struct RedisStore {
+    client: redis::Client,
+    connection_manager: OnceCell<ConnectionManager>,
+    }
+impl RedisStore {
+
+    const CHUNK_SIZE: usize=256*1024;
+
+    pub fn new(connection_info: redis::ConnectionInfo) -> Result<RedisStore, anyhow::Error> {
+        let client=redis::Client::open(connection_info)?;
+        let connection_manager=OnceCell::new();
+
+        Ok(RedisStore { client, connection_manager })
+        }
+
+    /// Returns a handle to the shared connection, establishing it on the first use.
+    /// Retrying only briefly, requests should rather fail fast than wait for an unreachable backend.
+    async fn connection(&self) -> Result<ConnectionManager, anyhow::Error> {
+        let connection_manager=self.connection_manager
+        .get_or_try_init(|| ConnectionManager::new_with_backoff(self.client.clone(), 2, 100, 2)).await
+        .map_err(RedisStore::map_error)?;
+
+        Ok(connection_manager.clone())
+        }
+
+    /// Converts connection related errors to BackendUnavailable.
+    fn map_error(e: redis::RedisError) -> anyhow::Error {
+        if e.is_connection_refusal() || e.is_connection_dropped() || e.is_io_error() || e.is_timeout() {
+            return BackendUnavailable(format!("{e}")).into();
+            }
+
+        e.into()
+        }
+
+    fn key(id: &str) -> String {
+        format!("clipboard::{id}")
+        }
+
+    /// Appends the content of a stream to a key in chunks, returning the number of appended bytes.
+    /// The key expires after the time to live, so an upload cut off midway doesn't stay behind forever.
+    async fn append_stream(connection: &mut ConnectionManager, key: &str, mut content: ContentStream, ttl: Duration) -> Result<usize, anyhow::Error> {
+        let mut size=0;
+        let mut chunk=Vec::with_capacity(RedisStore::CHUNK_SIZE);
+
+        loop {
+            let data=content.try_next().await?;
+            if let Some(data)=&data {
+                chunk.extend_from_slice(data);
+                size+=data.len();
+                }
+
+            if chunk.len()>=RedisStore::CHUNK_SIZE || (data.is_none() && !chunk.is_empty()) {
+                redis::pipe()
+                .append(key, &chunk).ignore()
+                .expire(key, ttl.as_secs() as usize).ignore()
+                .query_async::<_, ()>(connection).await.map_err(RedisStore::map_error)?;
+                chunk.clear();
+                }
+
+            if data.is_none() {
+                return Ok(size);
+                }
+            }
+        }
+    }
+#[async_trait]
+impl ClipboardStore for RedisStore {
+
+    async fn get(&self, id: &str) -> Result<Option<Vec<u8>>, anyhow::Error> {
+        let mut connection=self.connection().await?;
+        let content: Option<Vec<u8>>=connection.get(RedisStore::key(id)).await.map_err(RedisStore::map_error)?;
+
+        Ok(content)
+        }
+    async fn set(&self, id: &str, content: &[u8], ttl: Duration) -> Result<(), anyhow::Error> {
+        let mut connection=self.connection().await?;
+        connection.set_ex::<_, _, ()>(RedisStore::key(id), content, ttl.as_secs() as usize).await.map_err(RedisStore::map_error)?;
+
+        Ok(())
+        }
+    async fn delete(&self, id: &str) -> Result<(), anyhow::Error> {
+        let mut connection=self.connection().await?;
+        connection.del::<_, ()>(RedisStore::key(id)).await.map_err(RedisStore::map_error)?;
+
+        Ok(())
+        }
+    async fn expire(&self, id: &str, ttl: Duration) -> Result<bool, anyhow::Error> {
+        let mut connection=self.connection().await?;
+        let updated: bool=connection.expire(RedisStore::key(id), ttl.as_secs() as usize).await.map_err(RedisStore::map_error)?;
+
+        Ok(updated)
+        }
+    async fn list(&self) -> Result<Vec<StoredClipboard>, anyhow::Error> {
+        let mut connection=self.connection().await?;
+
+        let mut keys: Vec<String>=Vec::new();
+        let mut iter=connection.scan_match::<_, String>("clipboard::*").await.map_err(RedisStore::map_error)?;
+        while let Some(key)=iter.next_item().await {
+            keys.push(key);
+            }
+        drop(iter);
+
+        if keys.is_empty() {
+            return Ok(Vec::new());
+            }
+
+        let mut pipe=redis::pipe();
+        for key in &keys {
+            pipe.strlen(key).ttl(key);
+            }
+        let values: Vec<i64>=pipe.query_async(&mut connection).await.map_err(RedisStore::map_error)?;
+
+        let clipboards=keys.iter()
+        .zip(values.chunks(2))
+        .filter_map(|(key, values)| {
+            let (size, ttl)=(values[0], values[1]);
+
+            // -2 means the key expired in the meantime
+            if ttl==-2 {
+                return None;
+                }
+
+            let id=key.strip_prefix("clipboard::").unwrap_or(key);
+            let ttl=if ttl>=0 { Some(Duration::from_secs(ttl as u64)) } else { None };
+
+            Some(StoredClipboard::new(id, size as usize, ttl))
+            })
+        .collect();
+
+        Ok(clipboards)
+        }
+    async fn ping(&self) -> Result<(), anyhow::Error> {
+        let mut connection=self.connection().await?;
+        redis::cmd("PING").query_async::<_, ()>(&mut connection).await.map_err(RedisStore::map_error)?;
+
+        Ok(())
+        }
+    async fn size(&self, id: &str) -> Result<Option<usize>, anyhow::Error> {
+        let mut connection=self.connection().await?;
+        // Empty values are never stored, so 0 means the key does not exist
+        let size: usize=connection.strlen(RedisStore::key(id)).await.map_err(RedisStore::map_error)?;
+
+        Ok(Some(size).filter(|size| *size>0))
+        }
+    async fn get_stream(&self, id: &str) -> Result<Option<(usize, ContentStream)>, anyhow::Error> {
+        let Some(size)=self.size(id).await? else {
+            return Ok(None);
+            };
+
+        // Redis can't read a value at a fixed version in parts, so a replacement of the clipboard during the download cuts the stream off
+        // The replacement is recognized by the changed size, content of the same size is caught by the authenticated encryption of the clients
+        let connection=self.connection().await?;
+        let content_stream=stream::try_unfold((connection, RedisStore::key(id), 0), move |(mut connection, key, offset)| async move {
+            if offset>=size {
+                return Ok(None);
+                }
+
+            let end=(offset+RedisStore::CHUNK_SIZE).min(size);
+            let (chunk, current_size): (Vec<u8>, usize)=redis::pipe()
+            .getrange(&key, offset as isize, end as isize-1)
+            .strlen(&key)
+            .query_async(&mut connection).await.map_err(io::Error::other)?;
+            if current_size!=size || chunk.len()!=end-offset {
+                return Err(io::Error::other("Clipboard replaced during the download"));
+                }
+
+            Ok(Some((Bytes::from(chunk), (connection, key, end))))
+            })
+        .boxed();
+
+        Ok(Some((size, content_stream)))
+        }
+    async fn set_stream(&self, id: &str, content: ContentStream, ttl: Duration) -> Result<usize, anyhow::Error> {
+        let mut connection=self.connection().await?;
+        let upload_number: u64=connection.incr("clipboard_upload_counter", 1).await.map_err(RedisStore::map_error)?;
+        let upload_key=format!("clipboard_upload::{upload_number}");
+
+        let size=match RedisStore::append_stream(&mut connection, &upload_key, content, ttl).await {
+            Ok(size) => size,
+            Err(e) => {
+                let _: Result<(), _>=connection.del(&upload_key).await;
+                return Err(e);
+                },
+            };
+
+        // Nothing has been appended for empty content, so there is no key to replace the clipboard with
+        if size==0 {
+            self.set(id, &[], ttl).await?;
+            return Ok(0);
+            }
+
+        // Replacing the clipboard at once, so readers never see a partial upload
+        let key=RedisStore::key(id);
+        redis::pipe().atomic()
+        .rename(&upload_key, &key).ignore()
+        .expire(&key, ttl.as_secs() as usize).ignore()
+        .query_async::<_, ()>(&mut connection).await.map_err(RedisStore::map_error)?;
+
+        Ok(size)
+        }
+    }
+
+struct MemoryEntry {
+    content: Vec<u8>,
+    expires_at: Instant,
+    }
+
+/// A ClipboardStore keeping the clipboards in the server process.
+/// Suitable for small deployments and tests, the content is lost on restart.
+pub struct MemoryStore {
+    entries: Mutex<HashMap<String, MemoryEntry>>,
+    }
+impl MemoryStore {
+
+    pub fn new() -> MemoryStore {
+        let entries=Mutex::new(HashMap::new());
+
+        MemoryStore { entries }
+        }
+    }
+impl Default for MemoryStore {
+
+    fn default() -> MemoryStore {
+        MemoryStore::new()
+        }
+    }
+#[async_trait]
+impl ClipboardStore for MemoryStore {
+
+    async fn get(&self, id: &str) -> Result<Option<Vec<u8>>, anyhow::Error> {
+        let entries=self.entries.lock().await;
+
+        let content=entries.get(id)
+        .filter(|entry| entry.expires_at>Instant::now())
+        .map(|entry| entry.content.clone());
+
+        Ok(content)
+        }
+    async fn set(&self, id: &str, content: &[u8], ttl: Duration) -> Result<(), anyhow::Error> {
+        let mut entries=self.entries.lock().await;
+
+        entries.insert(id.to_string(), MemoryEntry {
+            content: content.to_vec(),
+            expires_at: Instant::now()+ttl,
+            });
+
+        Ok(())
+        }
+    async fn delete(&self, id: &str) -> Result<(), anyhow::Error> {
+        let mut entries=self.entries.lock().await;
+        entries.remove(id);
+
+        Ok(())
+        }
+    async fn expire(&self, id: &str, ttl: Duration) -> Result<bool, anyhow::Error> {
+        let mut entries=self.entries.lock().await;
+        let current_time=Instant::now();
+
+        let Some(entry)=entries.get_mut(id).filter(|entry| entry.expires_at>current_time) else {
+            return Ok(false);
+            };
+        entry.expires_at=current_time+ttl;
+
+        Ok(true)
+        }
+    async fn list(&self) -> Result<Vec<StoredClipboard>, anyhow::Error> {
+        let mut entries=self.entries.lock().await;
+        let current_time=Instant::now();
+
+        entries.retain(|_, entry| entry.expires_at>current_time);
+
+        let clipboards=entries.iter()
+        .map(|(id, entry)| StoredClipboard::new(id, entry.content.len(), Some(entry.expires_at-current_time)))
+        .collect();
+
+        Ok(clipboards)
+        }
+    async fn ping(&self) -> Result<(), anyhow::Error> {
+        Ok(())
+        }
+    async fn purge_expired(&self) -> Result<usize, anyhow::Error> {
+        let mut entries=self.entries.lock().await;
+        let current_time=Instant::now();
+
+        let original_count=entries.len();
+        entries.retain(|_, entry| entry.expires_at>current_time);
+
+        Ok(original_count-entries.len())
+        }
+    }
+
+/// An unfinished write of the FilesystemStore, removed when dropped unless persisted.
+/// Cleans up also after writes cancelled midway, i.e. by a client disconnecting during an upload.
+struct TemporaryFile {
+    path: PathBuf,
+    persisted: bool,
+    }
+impl TemporaryFile {
+
+    fn new(path: PathBuf) -> TemporaryFile {
+        TemporaryFile { path, persisted: false }
+        }
+
+    /// Moves the file to its final location.
+    async fn persist(mut self, path: PathBuf) -> Result<(), anyhow::Error> {
+        tokio::fs::rename(&self.path, path).await?;
+        self.persisted=true;
+
+        Ok(())
+        }
+    }
+impl Drop for TemporaryFile {
+
+    fn drop(&mut self) {
+        if !self.persisted {
+            let _=std::fs::remove_file(&self.path);
+            }
+        }
+    }
+
+/// A ClipboardStore keeping each clipboard in a file of a directory, so the content survives server restarts.
+/// A file starts with the expiration time of the clipboard (unix timestamp in seconds, 8 bytes big endian), followed by the content.
+pub struct FilesystemStore {
+    directory: PathBuf,
+    write_counter: AtomicU64,
+    }
+impl FilesystemStore {
+
+    const HEADER_SIZE: usize=8;
+
+    /// Opens the storage directory, removing unfinished writes left behind by a previous server process.
+    pub fn new(directory: PathBuf) -> Result<FilesystemStore, anyhow::Error> {
+        std::fs::create_dir_all(&directory)?;
+        let write_counter=AtomicU64::new(0);
+
+        for entry in std::fs::read_dir(&directory)? {
+            let entry=entry?;
+            if entry.file_name().to_str().is_some_and(|name| name.ends_with(".tmp")) && entry.file_type()?.is_file() {
+                std::fs::remove_file(entry.path())?;
+                }
+            }
+
+        Ok(FilesystemStore { directory, write_counter })
+        }
+
+    fn path(&self, id: &str) -> PathBuf {
+        self.directory.join(id)
+        }
+
+    /// Returns a unique path for an unfinished write, so concurrent writes to the same clipboard don't mix their content.
+    fn temporary_path(&self, id: &str) -> PathBuf {
+        let write_number=self.write_counter.fetch_add(1, Ordering::Relaxed);
+
+        self.directory.join(format!("{id}.{write_number}.tmp"))
+        }
+
+    fn expiration_header(ttl: Duration) -> Result<[u8; FilesystemStore::HEADER_SIZE], anyhow::Error> {
+        let expires_at=(SystemTime::now()+ttl).duration_since(UNIX_EPOCH)?.as_secs();
+
+        Ok(expires_at.to_be_bytes())
+        }
+
+    /// Writes the content of a clipboard to a temporary file, returning the number of written content bytes.
+    async fn write_temporary_file(path: &PathBuf, mut content: ContentStream, ttl: Duration) -> Result<usize, anyhow::Error> {
+        let mut file=tokio::fs::File::create(path).await?;
+        file.write_all(&FilesystemStore::expiration_header(ttl)?).await?;
+
+        let mut size=0;
+        while let Some(chunk)=content.try_next().await? {
+            file.write_all(&chunk).await?;
+            size+=chunk.len();
+            }
+        file.flush().await?;
+
+        Ok(size)
+        }
+
+    /// Reads the header of a clipboard file, returning its expiration time and content size.
+    async fn read_header(path: &PathBuf) -> Result<(SystemTime, usize), anyhow::Error> {
+        let mut file=tokio::fs::File::open(path).await?;
+        let file_size=file.metadata().await?.len() as usize;
+
+        let mut header=[0u8; FilesystemStore::HEADER_SIZE];
+        file.read_exact(&mut header).await?;
+
+        let expires_at=UNIX_EPOCH+Duration::from_secs(u64::from_be_bytes(header));
+
+        Ok((expires_at, file_size-FilesystemStore::HEADER_SIZE))
+        }
+
+    /// Returns paths of all clipboard files in the storage directory, skipping unfinished writes.
+    async fn clipboard_files(&self) -> Result<Vec<(String, PathBuf)>, anyhow::Error> {
+        let mut files=Vec::new();
+
+        let mut entries=tokio::fs::read_dir(&self.directory).await?;
+        while let Some(entry)=entries.next_entry().await? {
+            if let Some(id)=entry.file_name().to_str() {
+                if !id.ends_with(".tmp") && entry.file_type().await?.is_file() {
+                    files.push((id.to_string(), entry.path()));
+                    }
+                }
+            }
+
+        Ok(files)
+        }
+    }
+#[async_trait]
+impl ClipboardStore for FilesystemStore {
+
+    async fn get(&self, id: &str) -> Result<Option<Vec<u8>>, anyhow::Error> {
+        let data=match tokio::fs::read(self.path(id)).await {
+            Ok(data) => data,
+            Err(e) if e.kind()==ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+            };
+
+        if data.len()<FilesystemStore::HEADER_SIZE {
+            return Ok(None);
+            }
+
+        let expires_at=u64::from_be_bytes(data[..FilesystemStore::HEADER_SIZE].try_into()?);
+        if UNIX_EPOCH+Duration::from_secs(expires_at)<=SystemTime::now() {
+            return Ok(None);
+            }
+
+        Ok(Some(data[FilesystemStore::HEADER_SIZE..].to_vec()))
+        }
+    async fn set(&self, id: &str, content: &[u8], ttl: Duration) -> Result<(), anyhow::Error> {
+        let mut data=Vec::with_capacity(FilesystemStore::HEADER_SIZE+content.len());
+        data.extend_from_slice(&FilesystemStore::expiration_header(ttl)?);
+        data.extend_from_slice(content);
+
+        // Writing to a temporary file first, so readers never see a partially written clipboard
+        let temporary_file=TemporaryFile::new(self.temporary_path(id));
+        tokio::fs::write(&temporary_file.path, &data).await?;
+        temporary_file.persist(self.path(id)).await?;
+
+        Ok(())
+        }
+    async fn delete(&self, id: &str) -> Result<(), anyhow::Error> {
+        match tokio::fs::remove_file(self.path(id)).await {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind()==ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e.into()),
+            }
+        }
+    async fn expire(&self, id: &str, ttl: Duration) -> Result<bool, anyhow::Error> {
+        // Rewriting the header in place, a concurrent replacement of the file just gets its own expiration time
+        let mut file=match tokio::fs::OpenOptions::new().read(true).write(true).open(self.path(id)).await {
+            Ok(file) => file,
+            Err(e) if e.kind()==ErrorKind::NotFound => return Ok(false),
+            Err(e) => return Err(e.into()),
+            };
+
+        let mut header=[0u8; FilesystemStore::HEADER_SIZE];
+        if file.read_exact(&mut header).await.is_err() || UNIX_EPOCH+Duration::from_secs(u64::from_be_bytes(header))<=SystemTime::now() {
+            return Ok(false);
+            }
+
+        file.seek(SeekFrom::Start(0)).await?;
+        file.write_all(&FilesystemStore::expiration_header(ttl)?).await?;
+        file.flush().await?;
+
+        Ok(true)
+        }
+    async fn list(&self) -> Result<Vec<StoredClipboard>, anyhow::Error> {
+        let current_time=SystemTime::now();
+        let mut clipboards=Vec::new();
+
+        for (id, path) in self.clipboard_files().await? {
+            // The file may have been removed or be corrupted, neither should fail the whole listing
+            if let Ok((expires_at, size))=FilesystemStore::read_header(&path).await {
+                if let Ok(ttl)=expires_at.duration_since(current_time) {
+                    clipboards.push(StoredClipboard::new(&id, size, Some(ttl)));
+                    }
+                }
+            }
+
+        Ok(clipboards)
+        }
+    async fn ping(&self) -> Result<(), anyhow::Error> {
+        let metadata=tokio::fs::metadata(&self.directory).await?;
+        if metadata.permissions().readonly() {
+            anyhow::bail!("Storage directory {} is read-only", self.directory.display());
+            }
+
+        Ok(())
+        }
+    async fn purge_expired(&self) -> Result<usize, anyhow::Error> {
+        let current_time=SystemTime::now();
+        let mut purged_count=0;
+
+        for (id, path) in self.clipboard_files().await? {
+            let expired=match FilesystemStore::read_header(&path).await {
+                Ok((expires_at, _)) => expires_at<=current_time,
+                Err(_) => true,
+                };
+
+            if expired {
+                self.delete(&id).await?;
+                purged_count+=1;
+                }
+            }
+
+        Ok(purged_count)
+        }
+    async fn get_stream(&self, id: &str) -> Result<Option<(usize, ContentStream)>, anyhow::Error> {
+        // Reading the header and the content through the same handle, so a concurrent replacement can't mix two versions
+        let mut file=match tokio::fs::File::open(self.path(id)).await {
+            Ok(file) => file,
+            Err(e) if e.kind()==ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+            };
+        let file_size=file.metadata().await?.len() as usize;
+        if file_size<FilesystemStore::HEADER_SIZE {
+            return Ok(None);
+            }
+
+        let mut header=[0u8; FilesystemStore::HEADER_SIZE];
+        file.read_exact(&mut header).await?;
+        if UNIX_EPOCH+Duration::from_secs(u64::from_be_bytes(header))<=SystemTime::now() {
+            return Ok(None);
+            }
+
+        Ok(Some((file_size-FilesystemStore::HEADER_SIZE, ReaderStream::new(file).boxed())))
+        }
+    async fn set_stream(&self, id: &str, content: ContentStream, ttl: Duration) -> Result<usize, anyhow::Error> {
+        let temporary_file=TemporaryFile::new(self.temporary_path(id));
+        let size=FilesystemStore::write_temporary_file(&temporary_file.path, content, ttl).await?;
+        temporary_file.persist(self.path(id)).await?;
+
+        Ok(size)
+        }
+    }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn memory_store_sets_gets_and_deletes() {
+        let store=MemoryStore::new();
+        assert!(store.get("a").await.unwrap().is_none());
+
+        store.set("a", b"content", Duration::from_secs(60)).await.unwrap();
+        assert_eq!(store.get("a").await.unwrap().as_deref(), Some(&b"content"[..]));
+        assert_eq!(store.size("a").await.unwrap(), Some(7));
+
+        store.set("a", b"new", Duration::from_secs(60)).await.unwrap();
+        assert_eq!(store.get("a").await.unwrap().as_deref(), Some(&b"new"[..]));
+
+        store.delete("a").await.unwrap();
+        assert!(store.get("a").await.unwrap().is_none());
+        store.delete("a").await.unwrap();
+        }
+
+    #[tokio::test]
+    async fn memory_store_hides_and_purges_expired_clipboards() {
+        let store=MemoryStore::new();
+        store.set("expired", b"content", Duration::ZERO).await.unwrap();
+        store.set("valid", b"content", Duration::from_secs(60)).await.unwrap();
+
+        assert!(store.get("expired").await.unwrap().is_none());
+        assert!(store.get_stream("expired").await.unwrap().is_none());
+
+        assert_eq!(store.purge_expired().await.unwrap(), 1);
+        assert_eq!(store.purge_expired().await.unwrap(), 0);
+        assert!(store.get("valid").await.unwrap().is_some());
+        }
+
+    #[tokio::test]
+    async fn memory_store_expires_clipboards() {
+        let store=MemoryStore::new();
+        store.set("a", b"content", Duration::ZERO).await.unwrap();
+        assert!(!store.expire("a", Duration::from_secs(60)).await.unwrap());
+        assert!(!store.expire("b", Duration::from_secs(60)).await.unwrap());
+
+        store.set("a", b"content", Duration::from_secs(60)).await.unwrap();
+        assert!(store.expire("a", Duration::ZERO).await.unwrap());
+        assert!(store.get("a").await.unwrap().is_none());
+        }
+
+    #[tokio::test]
+    async fn memory_store_lists_valid_clipboards() {
+        let store=MemoryStore::new();
+        store.set("expired", b"content", Duration::ZERO).await.unwrap();
+        store.set("valid", b"content", Duration::from_secs(60)).await.unwrap();
+
+        let clipboards=store.list().await.unwrap();
+        assert_eq!(clipboards.len(), 1);
+        assert_eq!(clipboards[0].id(), "valid");
+        assert_eq!(clipboards[0].size(), 7);
+        assert!(clipboards[0].ttl().is_some_and(|ttl| ttl<=Duration::from_secs(60)));
+        }
+
+    #[tokio::test]
+    async fn memory_store_streams_content() {
+        let store=MemoryStore::new();
+        let content_stream=stream::iter([Ok(Bytes::from_static(b"con")), Ok(Bytes::from_static(b"tent"))]).boxed();
+
+        assert_eq!(store.set_stream("a", content_stream, Duration::from_secs(60)).await.unwrap(), 7);
+
+        let (size, content_stream)=store.get_stream("a").await.unwrap().unwrap();
+        let content: Vec<Bytes>=content_stream.try_collect().await.unwrap();
+        assert_eq!(size, 7);
+        assert_eq!(content.concat(), b"content");
+        }
+
+    fn filesystem_store() -> (tempfile::TempDir, FilesystemStore) {
+        let directory=tempfile::tempdir().unwrap();
+        let store=FilesystemStore::new(directory.path().to_path_buf()).unwrap();
+
+        (directory, store)
+        }
+
+    #[tokio::test]
+    async fn filesystem_store_writes_the_expiration_header() {
+        let (directory, store)=filesystem_store();
+        store.set("a", b"content", Duration::from_secs(60)).await.unwrap();
+
+        let data=std::fs::read(directory.path().join("a")).unwrap();
+        let expires_at=UNIX_EPOCH+Duration::from_secs(u64::from_be_bytes(data[..FilesystemStore::HEADER_SIZE].try_into().unwrap()));
+        assert_eq!(&data[FilesystemStore::HEADER_SIZE..], b"content");
+        assert!(expires_at>SystemTime::now() && expires_at<=SystemTime::now()+Duration::from_secs(61));
+        }
+
+    #[tokio::test]
+    async fn filesystem_store_sets_gets_and_deletes() {
+        let (_directory, store)=filesystem_store();
+        assert!(store.get("a").await.unwrap().is_none());
+
+        store.set("a", b"content", Duration::from_secs(60)).await.unwrap();
+        assert_eq!(store.get("a").await.unwrap().as_deref(), Some(&b"content"[..]));
+        assert_eq!(store.size("a").await.unwrap(), Some(7));
+
+        store.set("a", b"new", Duration::from_secs(60)).await.unwrap();
+        assert_eq!(store.get("a").await.unwrap().as_deref(), Some(&b"new"[..]));
+
+        store.delete("a").await.unwrap();
+        assert!(store.get("a").await.unwrap().is_none());
+        store.delete("a").await.unwrap();
+        }
+
+    #[tokio::test]
+    async fn filesystem_store_expires_clipboards() {
+        let (_directory, store)=filesystem_store();
+        store.set("a", b"content", Duration::ZERO).await.unwrap();
+        assert!(store.get("a").await.unwrap().is_none());
+        assert!(!store.expire("a", Duration::from_secs(60)).await.unwrap());
+        assert!(!store.expire("b", Duration::from_secs(60)).await.unwrap());
+
+        store.set("a", b"content", Duration::from_secs(60)).await.unwrap();
+        assert!(store.expire("a", Duration::ZERO).await.unwrap());
+        assert!(store.get("a").await.unwrap().is_none());
+        }
+
+    #[tokio::test]
+    async fn filesystem_store_lists_and_purges_expired_clipboards() {
+        let (directory, store)=filesystem_store();
+        store.set("expired", b"content", Duration::ZERO).await.unwrap();
+        store.set("valid", b"content", Duration::from_secs(60)).await.unwrap();
+        std::fs::write(directory.path().join("corrupted"), b"abc").unwrap();
+
+        let clipboards=store.list().await.unwrap();
+        assert_eq!(clipboards.len(), 1);
+        assert_eq!(clipboards[0].id(), "valid");
+        assert_eq!(clipboards[0].size(), 7);
+        assert!(clipboards[0].ttl().is_some_and(|ttl| ttl<=Duration::from_secs(60)));
+
+        assert_eq!(store.purge_expired().await.unwrap(), 2);
+        assert_eq!(store.purge_expired().await.unwrap(), 0);
+        assert!(store.get("valid").await.unwrap().is_some());
+        }
+
+    #[tokio::test]
+    async fn filesystem_store_streams_content() {
+        let (directory, store)=filesystem_store();
+        let content_stream=stream::iter([Ok(Bytes::from_static(b"con")), Ok(Bytes::from_static(b"tent"))]).boxed();
+
+        assert_eq!(store.set_stream("a", content_stream, Duration::from_secs(60)).await.unwrap(), 7);
+
+        let (size, content_stream)=store.get_stream("a").await.unwrap().unwrap();
+        let content: Vec<Bytes>=content_stream.try_collect().await.unwrap();
+        assert_eq!(size, 7);
+        assert_eq!(content.concat(), b"content");
+        assert_eq!(std::fs::read_dir(directory.path()).unwrap().count(), 1);
+        }
+
+    #[tokio::test]
+    async fn filesystem_store_removes_failed_writes() {
+        let (directory, store)=filesystem_store();
+        store.set("a", b"content", Duration::from_secs(60)).await.unwrap();
+
+        let content_stream=stream::iter([Ok(Bytes::from_static(b"new")), Err(io::Error::other("Upload failed"))]).boxed();
+        assert!(store.set_stream("a", content_stream, Duration::from_secs(60)).await.is_err());
+
+        // The previous content stays in place and no temporary file is left behind
+        assert_eq!(store.get("a").await.unwrap().as_deref(), Some(&b"content"[..]));
+        assert_eq!(std::fs::read_dir(directory.path()).unwrap().count(), 1);
+        }
+
+    #[tokio::test]
+    async fn filesystem_store_removes_stale_temporary_files_on_startup() {
+        let (directory, store)=filesystem_store();
+        store.set("a", b"content", Duration::from_secs(60)).await.unwrap();
+        std::fs::write(directory.path().join("a.0.tmp"), b"stale").unwrap();
+
+        let store=FilesystemStore::new(directory.path().to_path_buf()).unwrap();
+        assert!(!directory.path().join("a.0.tmp").exists());
+        assert_eq!(store.get("a").await.unwrap().as_deref(), Some(&b"content"[..]));
+        }
+    }
+